@@ -0,0 +1,157 @@
+use crate::data_model;
+use crate::i18n;
+use crate::world;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::error;
+use std::fs;
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+const MAX_USERNAME_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Account {
+    pub password_hash: String,
+    pub player: Option<world::Entity>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Accounts {
+    writer: Option<mpsc::Sender<String>>,
+    accounts: HashMap<String, Account>,
+}
+
+pub fn hash_password(password: &str) -> Result<String, data_model::WorldError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|error| {
+        println!("Could not hash password: {}", error);
+        data_model::WorldError::Internal
+    })
+}
+
+// Unknown usernames still pay for a hash, otherwise the response time would
+// tell which usernames exist.
+pub fn verify_password(
+    password: &str,
+    password_hash: Option<String>,
+) -> Result<(), data_model::WorldError> {
+    match password_hash {
+        Some(password_hash) => match bcrypt::verify(password, password_hash.as_str()) {
+            Ok(true) => Ok(()),
+            _ => Err(data_model::WorldError::BadCredentials),
+        },
+        None => {
+            bcrypt::hash(password, bcrypt::DEFAULT_COST).ok();
+            Err(data_model::WorldError::BadCredentials)
+        }
+    }
+}
+
+// Writes happen on their own thread so that callers holding the world lock
+// only pay for serializing the accounts. Only the latest pending data is
+// written when saves pile up.
+fn spawn_writer(path: String) -> mpsc::Sender<String> {
+    let (sender, receiver) = mpsc::channel::<String>();
+
+    thread::spawn(move || {
+        while let Ok(data) = receiver.recv() {
+            let data = receiver.try_iter().last().unwrap_or(data);
+            let tmp_path = format!("{}.tmp", path);
+
+            if let Err(error) =
+                fs::write(&tmp_path, data).and_then(|_| fs::rename(&tmp_path, &path))
+            {
+                println!("Could not save accounts: {}", error);
+            }
+        }
+    });
+
+    sender
+}
+
+impl Accounts {
+    pub fn load(path: String) -> Result<Accounts, Box<dyn error::Error>> {
+        let accounts = match fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(data.as_str())?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(Box::new(error)),
+        };
+
+        Ok(Accounts {
+            writer: Some(spawn_writer(path)),
+            accounts,
+        })
+    }
+
+    fn save_or_log(&self) {
+        if let Some(writer) = &self.writer {
+            match serde_json::to_string(&self.accounts) {
+                Ok(data) => {
+                    writer.send(data).ok();
+                }
+                Err(error) => println!("Could not save accounts: {}", error),
+            }
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        username: String,
+        password_hash: String,
+    ) -> Result<(), data_model::WorldError> {
+        if username.is_empty()
+            || username.len() > MAX_USERNAME_LENGTH
+            || !username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(data_model::WorldError::BadRequest(
+                i18n::Message::InvalidUsername(MAX_USERNAME_LENGTH),
+            ));
+        }
+
+        if self.accounts.contains_key(&username) {
+            return Err(data_model::WorldError::Other(i18n::Message::UsernameTaken));
+        }
+
+        self.accounts.insert(
+            username,
+            Account {
+                password_hash,
+                player: None,
+            },
+        );
+        self.save_or_log();
+
+        Ok(())
+    }
+
+    pub fn password_hash(&self, username: &str) -> Option<String> {
+        self.accounts
+            .get(username)
+            .map(|account| account.password_hash.clone())
+    }
+
+    pub fn saved_player(&self, username: &str) -> Option<world::Entity> {
+        self.accounts
+            .get(username)
+            .and_then(|account| account.player.clone())
+    }
+
+    pub fn store_players(&mut self, players: Vec<(String, Option<world::Entity>)>) {
+        if players.is_empty() {
+            return;
+        }
+
+        for (username, player) in players.into_iter() {
+            if let Some(account) = self.accounts.get_mut(&username) {
+                account.player = player;
+            }
+        }
+
+        self.save_or_log();
+    }
+}
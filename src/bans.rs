@@ -0,0 +1,513 @@
+use crate::data_model;
+use crate::i18n;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net;
+use std::str::FromStr;
+use std::time;
+
+static EXPIRY_PREFIX: &str = "expires=";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Network {
+    address: net::IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn bits(address: &net::IpAddr) -> (u128, u8) {
+        match address {
+            net::IpAddr::V4(address) => (u32::from(*address) as u128, 32),
+            net::IpAddr::V6(address) => (u128::from(*address), 128),
+        }
+    }
+
+    pub fn contains(&self, ip: &net::IpAddr) -> bool {
+        if self.address.is_ipv4() != ip.is_ipv4() {
+            return false;
+        }
+
+        let (network, width) = Network::bits(&self.address);
+        let (ip, _) = Network::bits(ip);
+        let shift = (width - self.prefix) as u32;
+
+        network.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
+
+    // Clears the host bits, so that `10.0.0.1/8` is the same network as
+    // `10.0.0.0/8`.
+    fn masked(address: net::IpAddr, prefix: u8) -> net::IpAddr {
+        let (bits, width) = Network::bits(&address);
+        let host_bits = (width - prefix) as u32;
+        let bits = bits
+            .checked_shr(host_bits)
+            .and_then(|bits| bits.checked_shl(host_bits))
+            .unwrap_or(0);
+
+        match address {
+            net::IpAddr::V4(_) => net::IpAddr::V4(net::Ipv4Addr::from(bits as u32)),
+            net::IpAddr::V6(_) => net::IpAddr::V6(net::Ipv6Addr::from(bits)),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = i18n::Message;
+
+    fn from_str(s: &str) -> Result<Network, i18n::Message> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+
+        let address = net::IpAddr::from_str(address)
+            .map_err(|_| i18n::Message::InvalidAddress(String::from(address)))?;
+        let (_, width) = Network::bits(&address);
+
+        let prefix = match prefix {
+            Some(prefix) => match u8::from_str(prefix) {
+                Ok(prefix) if prefix <= width => prefix,
+                _ => return Err(i18n::Message::InvalidPrefix(String::from(prefix))),
+            },
+            None => width,
+        };
+
+        Ok(Network {
+            address: Network::masked(address, prefix),
+            prefix,
+        })
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, width) = Network::bits(&self.address);
+
+        if self.prefix == width {
+            write!(f, "{}", self.address)
+        } else {
+            write!(f, "{}/{}", self.address, self.prefix)
+        }
+    }
+}
+
+// One line of the ban list: `<ip>[/<prefix>] [expires=<unix timestamp>] [reason]`
+// The reason can be wrapped in double quotes, which is how reasons that could
+// be mistaken for an expiry are saved.
+#[derive(Clone, Debug)]
+pub struct BanEntry {
+    pub network: Network,
+    pub expires: Option<u64>,
+    pub reason: Option<String>,
+}
+
+impl BanEntry {
+    pub fn from_data_model(ban: data_model::Ban) -> Result<BanEntry, i18n::Message> {
+        // Each entry is one line of the ban file.
+        if let Some(reason) = &ban.reason {
+            if reason.chars().any(char::is_control) {
+                return Err(i18n::Message::InvalidBanReason);
+            }
+        }
+
+        Ok(BanEntry {
+            network: Network::from_str(ban.ip.as_str())?,
+            expires: ban.expires,
+            reason: ban.reason,
+        })
+    }
+
+    pub fn to_data_model(&self) -> data_model::Ban {
+        data_model::Ban {
+            ip: self.network.to_string(),
+            expires: self.expires,
+            reason: self.reason.clone(),
+        }
+    }
+
+    fn is_active(&self, now: u64) -> bool {
+        match self.expires {
+            Some(expires) => expires > now,
+            None => true,
+        }
+    }
+}
+
+impl FromStr for BanEntry {
+    type Err = i18n::Message;
+
+    fn from_str(s: &str) -> Result<BanEntry, i18n::Message> {
+        let mut fields = s.splitn(2, char::is_whitespace);
+        let network = Network::from_str(fields.next().unwrap_or(""))?;
+        let mut rest = fields.next().unwrap_or("").trim();
+
+        let mut expires = None;
+        if let Some(timestamp) = rest.strip_prefix(EXPIRY_PREFIX) {
+            let mut fields = timestamp.splitn(2, char::is_whitespace);
+            let timestamp = fields.next().unwrap_or("");
+
+            expires = Some(
+                u64::from_str(timestamp)
+                    .map_err(|_| i18n::Message::InvalidExpiry(String::from(timestamp)))?,
+            );
+            rest = fields.next().unwrap_or("").trim();
+        }
+
+        if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+            rest = &rest[1..rest.len() - 1];
+        }
+
+        Ok(BanEntry {
+            network,
+            expires,
+            reason: if rest.is_empty() {
+                None
+            } else {
+                Some(String::from(rest))
+            },
+        })
+    }
+}
+
+impl fmt::Display for BanEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.network)?;
+
+        if let Some(expires) = self.expires {
+            write!(f, " {}{}", EXPIRY_PREFIX, expires)?;
+        }
+
+        if let Some(reason) = &self.reason {
+            if reason.starts_with(EXPIRY_PREFIX)
+                || reason.starts_with('"')
+                || reason != reason.trim()
+            {
+                write!(f, " \"{}\"", reason)?;
+            } else {
+                write!(f, " {}", reason)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn modified(path: &str) -> Option<time::SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Comments, blank and invalid lines are kept to be written back as they were.
+#[derive(Clone, Debug)]
+enum Line {
+    Entry(BanEntry),
+    Other(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BanList {
+    path: Option<String>,
+    modified: Option<time::SystemTime>,
+    lines: Vec<Line>,
+}
+
+impl BanList {
+    pub fn load(path: String) -> io::Result<BanList> {
+        let mut ban_list = BanList {
+            path: Some(path),
+            modified: None,
+            lines: Vec::new(),
+        };
+        ban_list.reload()?;
+
+        Ok(ban_list)
+    }
+
+    fn reload(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error),
+        };
+
+        let mut lines = Vec::new();
+
+        for (index, line) in data.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                lines.push(Line::Other(String::from(line)));
+                continue;
+            }
+
+            match BanEntry::from_str(trimmed) {
+                Ok(entry) => lines.push(Line::Entry(entry)),
+                Err(error) => {
+                    println!(
+                        "Ignoring line {} of ban list {}: {}",
+                        index + 1,
+                        path,
+                        error.translate(i18n::Lang::En)
+                    );
+                    lines.push(Line::Other(String::from(line)));
+                }
+            }
+        }
+
+        self.lines = lines;
+        self.modified = modified(&path);
+
+        Ok(())
+    }
+
+    pub fn reload_if_changed(&mut self) -> io::Result<bool> {
+        let changed = match &self.path {
+            Some(path) => modified(path) != self.modified,
+            None => false,
+        };
+
+        if changed {
+            self.reload()?;
+        }
+
+        Ok(changed)
+    }
+
+    fn save(&mut self) -> io::Result<()> {
+        match self.path.clone() {
+            Some(path) => {
+                let mut data = String::new();
+                for line in self.lines.iter() {
+                    match line {
+                        Line::Entry(entry) => data.push_str(&format!("{}\n", entry)),
+                        Line::Other(line) => data.push_str(&format!("{}\n", line)),
+                    }
+                }
+
+                let tmp_path = format!("{}.tmp", path);
+
+                fs::write(&tmp_path, data)?;
+                fs::rename(&tmp_path, &path)?;
+                self.modified = modified(&path);
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn all_entries(&self) -> impl Iterator<Item = &BanEntry> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            Line::Other(_) => None,
+        })
+    }
+
+    pub fn find(&self, ip: &net::IpAddr) -> Option<&BanEntry> {
+        let now = now();
+
+        self.all_entries()
+            .find(|entry| entry.is_active(now) && entry.network.contains(ip))
+    }
+
+    pub fn entries(&self) -> Vec<BanEntry> {
+        let now = now();
+
+        self.all_entries()
+            .filter(|entry| entry.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    pub fn ban(&mut self, entry: BanEntry) -> io::Result<bool> {
+        let now = now();
+        self.lines.retain(|line| match line {
+            Line::Entry(banned) => banned.is_active(now),
+            Line::Other(_) => true,
+        });
+
+        if self
+            .all_entries()
+            .any(|banned| banned.network == entry.network)
+        {
+            return Ok(false);
+        }
+
+        self.lines.push(Line::Entry(entry));
+        self.save()?;
+
+        Ok(true)
+    }
+
+    pub fn unban(&mut self, network: &Network) -> io::Result<bool> {
+        let length = self.lines.len();
+        self.lines.retain(|line| match line {
+            Line::Entry(entry) => &entry.network != network,
+            Line::Other(_) => true,
+        });
+
+        if self.lines.len() == length {
+            return Ok(false);
+        }
+
+        self.save()?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> net::IpAddr {
+        net::IpAddr::from_str(s).unwrap()
+    }
+
+    fn contains(network: &str, address: &str) -> bool {
+        Network::from_str(network).unwrap().contains(&ip(address))
+    }
+
+    #[test]
+    fn networks_contain_their_addresses() {
+        assert!(contains("192.168.1.7", "192.168.1.7"));
+        assert!(!contains("192.168.1.7", "192.168.1.8"));
+        assert!(contains("10.0.0.0/8", "10.255.3.4"));
+        assert!(!contains("10.0.0.0/8", "11.0.0.1"));
+        assert!(contains("0.0.0.0/0", "203.0.113.9"));
+        assert!(!contains("0.0.0.0/0", "::1"));
+    }
+
+    #[test]
+    fn ipv6_networks_contain_their_addresses() {
+        assert!(contains("2001:db8::/32", "2001:db8:1234::1"));
+        assert!(!contains("2001:db8::/32", "2001:db9::1"));
+        assert!(contains("::/0", "fe80::1"));
+        assert!(contains("::1", "::1"));
+        assert!(!contains("::/0", "127.0.0.1"));
+    }
+
+    #[test]
+    fn rejects_invalid_networks() {
+        assert!(Network::from_str("10.0.0.0/33").is_err());
+        assert!(Network::from_str("::/129").is_err());
+        assert!(Network::from_str("10.0.0/8").is_err());
+        assert_eq!(
+            Network::from_str("10.0.0.0/32").unwrap().to_string(),
+            "10.0.0.0"
+        );
+    }
+
+    #[test]
+    fn parses_ban_entries() {
+        let entry = BanEntry::from_str("10.0.0.0/8 expires=1700000000 spam and abuse").unwrap();
+        assert_eq!(entry.network, Network::from_str("10.0.0.0/8").unwrap());
+        assert_eq!(entry.expires, Some(1700000000));
+        assert_eq!(entry.reason.as_deref(), Some("spam and abuse"));
+        assert_eq!(
+            entry.to_string(),
+            "10.0.0.0/8 expires=1700000000 spam and abuse"
+        );
+
+        let entry = BanEntry::from_str("::1").unwrap();
+        assert_eq!(entry.expires, None);
+        assert_eq!(entry.reason, None);
+
+        let entry = BanEntry::from_str("127.0.0.1 griefing").unwrap();
+        assert_eq!(entry.expires, None);
+        assert_eq!(entry.reason.as_deref(), Some("griefing"));
+
+        assert!(BanEntry::from_str("127.0.0.1 expires=soon").is_err());
+        assert!(BanEntry::from_str("not-an-ip").is_err());
+    }
+
+    #[test]
+    fn rejects_reasons_spanning_several_lines() {
+        let ban = data_model::Ban {
+            ip: String::from("127.0.0.1"),
+            expires: None,
+            reason: Some(String::from("spam\n0.0.0.0/0")),
+        };
+
+        assert!(BanEntry::from_data_model(ban).is_err());
+    }
+
+    #[test]
+    fn ignores_expired_entries() {
+        let ban_list = BanList {
+            lines: vec![
+                Line::Entry(BanEntry::from_str("10.0.0.1 expires=1").unwrap()),
+                Line::Entry(BanEntry::from_str("10.0.0.2").unwrap()),
+            ],
+            ..BanList::default()
+        };
+
+        assert!(ban_list.find(&ip("10.0.0.1")).is_none());
+        assert!(ban_list.find(&ip("10.0.0.2")).is_some());
+        assert_eq!(ban_list.entries().len(), 1);
+    }
+
+    #[test]
+    fn host_bits_are_cleared() {
+        assert_eq!(
+            Network::from_str("10.0.0.1/8").unwrap(),
+            Network::from_str("10.0.0.0/8").unwrap()
+        );
+        assert_eq!(
+            Network::from_str("2001:db8::1/32").unwrap().to_string(),
+            "2001:db8::/32"
+        );
+        assert_eq!(
+            Network::from_str("203.0.113.9/0").unwrap().to_string(),
+            "0.0.0.0/0"
+        );
+    }
+
+    #[test]
+    fn reasons_survive_a_round_trip() {
+        for reason in ["expires=tomorrow", "\"quoted\"", " padded", "plain words"].iter() {
+            let entry = BanEntry {
+                network: Network::from_str("127.0.0.1").unwrap(),
+                expires: Some(1700000000),
+                reason: Some(reason.to_string()),
+            };
+            let parsed = BanEntry::from_str(&entry.to_string()).unwrap();
+
+            assert_eq!(parsed.expires, Some(1700000000));
+            assert_eq!(parsed.reason.as_deref(), Some(*reason));
+        }
+    }
+
+    #[test]
+    fn saving_keeps_comments_and_unknown_lines() {
+        let path = std::env::temp_dir().join(format!("mungeon-bans-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        fs::write(&path, "# Spammers\n10.0.0.0/8\n\nnot-an-ip\n").unwrap();
+
+        let mut ban_list = BanList::load(path.clone()).unwrap();
+        ban_list
+            .ban(BanEntry::from_str("192.168.0.1 griefing").unwrap())
+            .unwrap();
+        ban_list
+            .unban(&Network::from_str("10.0.0.0/8").unwrap())
+            .unwrap();
+        let data = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(data, "# Spammers\n\nnot-an-ip\n192.168.0.1 griefing\n");
+    }
+}
@@ -0,0 +1,132 @@
+use serde::Deserialize;
+
+use std::error;
+use std::fs;
+use std::path;
+
+static DEFAULT_CONFIG_PATH: &str = "mungeon.toml";
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: String,
+    pub port: u16,
+    pub telnet: Option<String>,
+    pub ban_list: String,
+    pub afk_timeout: u64,
+    pub workers: u16,
+    pub max_streams: usize,
+    pub secure_cookies: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            address: String::from("localhost"),
+            port: 8000,
+            telnet: None,
+            ban_list: String::from("ban_list.txt"),
+            afk_timeout: 60,
+            workers: 32,
+            max_streams: 16,
+            secure_cookies: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerConfig {
+    pub hp: u32,
+    pub dp: u32,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> PlayerConfig {
+        PlayerConfig { hp: 100, dp: 10 }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub save: String,
+    pub accounts: String,
+}
+
+impl Default for PathsConfig {
+    fn default() -> PathsConfig {
+        PathsConfig {
+            save: String::from("world_save.json"),
+            accounts: String::from("accounts.json"),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub movement: u32,
+    pub attack: u32,
+    pub look: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            movement: 5,
+            attack: 2,
+            look: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub player: PlayerConfig,
+    pub paths: PathsConfig,
+    pub admin: AdminConfig,
+    pub rate_limits: RateLimitConfig,
+}
+
+impl Config {
+    pub fn load(path: Option<String>) -> Result<Config, Box<dyn error::Error>> {
+        let path = match path {
+            Some(path) => path,
+            None if path::Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                String::from(DEFAULT_CONFIG_PATH)
+            }
+            None => return Ok(Config::default()),
+        };
+
+        let data = fs::read_to_string(&path)?;
+        let config: Config = toml::from_str(data.as_str())?;
+
+        println!("Loaded configuration from {}", path);
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misspelled_keys_are_rejected() {
+        let config: Config = toml::from_str("[server]\nport = 9000\n").unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.player.hp, PlayerConfig::default().hp);
+
+        assert!(toml::from_str::<Config>("[server]\nprot = 9000\n").is_err());
+        assert!(toml::from_str::<Config>("[rate_limit]\nmovement = 1\n").is_err());
+    }
+}
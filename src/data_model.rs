@@ -1,30 +1,106 @@
+use crate::i18n;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
 pub enum WorldError {
     Disappeared,
     Wall,
+    Locked(i18n::Message),
     DiffRoom,
+    Unauthorized,
+    BadCredentials,
     EntityNotFound(String),
-    Other(String),
+    ItemNotFound(String),
+    RateLimited,
+    TooManyStreams,
+    Banned(Option<String>),
+    BadRequest(i18n::Message),
+    Unprocessable,
+    NotFound,
+    Internal,
+    Other(i18n::Message),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ErrorBody {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
 }
 
 impl WorldError {
-    pub fn to_json_string(&self) -> String {
+    pub fn kind(&self) -> &'static str {
         match self {
-            WorldError::Disappeared => String::from(
-                r#"{ "type": "MORT", "message": "You were either killed or disconnected. In any case you disappeared ¯\\_(ツ)_/¯" }"#,
-            ),
-            WorldError::Wall => String::from(
-                r#"{ "type": "MUR", "message": "Ouch! You bumped into a wall! Consider yourself lucky I didn't make you lose HP when it happens... Now look where you're going!" }"#,
-            ),
-            WorldError::DiffRoom => String::from(
-                r#"{ "type": "DIFFSALLE", "message": "Room mismatch! What happened? Did you try to hack the game?" }"#,
-            ),
-            WorldError::EntityNotFound(_) => {
-                String::from("Entity was not found in world. Maybe it died or was disconnected")
+            WorldError::Disappeared => "MORT",
+            WorldError::Wall => "MUR",
+            WorldError::Locked(_) => "VERROUILLE",
+            WorldError::DiffRoom => "DIFFSALLE",
+            WorldError::Unauthorized => "NONAUTORISE",
+            WorldError::BadCredentials => "IDENTIFIANTS",
+            WorldError::EntityNotFound(_) => "ENTITEINTROUVABLE",
+            WorldError::ItemNotFound(_) => "OBJETINTROUVABLE",
+            WorldError::RateLimited => "TROPRAPIDE",
+            WorldError::TooManyStreams => "TROPDEFLUX",
+            WorldError::Banned(_) => "BANNI",
+            WorldError::BadRequest(_) => "REQUETEINVALIDE",
+            WorldError::Unprocessable => "CORPSINVALIDE",
+            WorldError::NotFound => "INTROUVABLE",
+            WorldError::Internal => "ERREURINTERNE",
+            WorldError::Other(_) => "AUTRE",
+        }
+    }
+
+    pub fn to_json_string(&self, lang: i18n::Lang) -> String {
+        serde_json::to_string(&ErrorBody {
+            kind: String::from(self.kind()),
+            message: self.message_in(lang),
+        })
+        .unwrap()
+    }
+
+    pub fn message(&self) -> String {
+        self.message_in(i18n::Lang::default())
+    }
+
+    pub fn message_in(&self, lang: i18n::Lang) -> String {
+        let message = match self {
+            WorldError::Disappeared => i18n::Message::Disappeared,
+            WorldError::Wall => i18n::Message::Wall,
+            WorldError::Locked(message) => message.clone(),
+            WorldError::DiffRoom => i18n::Message::DiffRoom,
+            WorldError::Unauthorized => i18n::Message::Unauthorized,
+            WorldError::BadCredentials => i18n::Message::BadCredentials,
+            WorldError::EntityNotFound(_) => i18n::Message::EntityNotFound,
+            WorldError::ItemNotFound(_) => i18n::Message::ItemNotFound,
+            WorldError::RateLimited => i18n::Message::RateLimited,
+            WorldError::TooManyStreams => i18n::Message::TooManyStreams,
+            WorldError::Banned(reason) => i18n::Message::Banned(reason.clone()),
+            WorldError::BadRequest(message) | WorldError::Other(message) => message.clone(),
+            WorldError::Unprocessable => i18n::Message::Unprocessable,
+            WorldError::NotFound => i18n::Message::NotFound,
+            WorldError::Internal => i18n::Message::Internal,
+        };
+
+        message.translate(lang)
+    }
+
+    pub fn status(&self) -> rocket::http::Status {
+        match self {
+            WorldError::Unauthorized | WorldError::BadCredentials => {
+                rocket::http::Status::Unauthorized
             }
-            WorldError::Other(msg) => format!("{{ \"message\": \"{}\" }}", msg),
+            WorldError::Disappeared => rocket::http::Status::Gone,
+            WorldError::EntityNotFound(_) | WorldError::ItemNotFound(_) | WorldError::NotFound => {
+                rocket::http::Status::NotFound
+            }
+            WorldError::Banned(_) => rocket::http::Status::Forbidden,
+            WorldError::RateLimited => rocket::http::Status::TooManyRequests,
+            WorldError::TooManyStreams => rocket::http::Status::ServiceUnavailable,
+            WorldError::BadRequest(_) => rocket::http::Status::BadRequest,
+            WorldError::Unprocessable => rocket::http::Status::UnprocessableEntity,
+            WorldError::Internal => rocket::http::Status::InternalServerError,
+            _ => rocket::http::Status::raw(409),
         }
     }
 
@@ -44,9 +120,11 @@ impl WorldError {
 
 impl<'r> rocket::response::Responder<'r> for WorldError {
     fn respond_to(self, req: &rocket::Request<'_>) -> rocket::response::Result<'r> {
-        rocket::Response::build_from(self.to_json_string().respond_to(req).unwrap())
+        let lang = req.guard::<i18n::Lang>().succeeded().unwrap_or_default();
+
+        rocket::Response::build_from(self.to_json_string(lang).respond_to(req).unwrap())
             .header(rocket::http::ContentType::JSON)
-            .status(rocket::http::Status::raw(409))
+            .status(self.status())
             .ok()
     }
 }
@@ -75,6 +153,47 @@ pub struct Room {
     pub paths: Vec<Direction>,
     #[serde(rename = "entites")]
     pub entities: Vec<String>,
+    #[serde(rename = "objets")]
+    pub items: Vec<Item>,
+    #[serde(rename = "annonces", default, skip_serializing_if = "Vec::is_empty")]
+    pub announcements: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ItemType {
+    #[serde(rename = "ARME")]
+    Weapon,
+    #[serde(rename = "ARMURE")]
+    Armour,
+    #[serde(rename = "POTION")]
+    Potion,
+    #[serde(rename = "CLE")]
+    Key,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Item {
+    pub guid: String,
+    pub description: String,
+    pub r#type: ItemType,
+    #[serde(rename = "valeur")]
+    pub value: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Inventory {
+    #[serde(rename = "objets")]
+    pub items: Vec<Item>,
+    #[serde(rename = "arme")]
+    pub weapon: Option<String>,
+    #[serde(rename = "armure")]
+    pub armour: Option<String>,
+    #[serde(rename = "degats")]
+    pub dp: u32,
+    #[serde(rename = "vie")]
+    pub life: u32,
+    #[serde(rename = "totalvie")]
+    pub total_life: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -87,6 +206,23 @@ pub enum Direction {
     S,
     #[serde(rename = "W")]
     W,
+    #[serde(rename = "U")]
+    U,
+    #[serde(rename = "D")]
+    D,
+}
+
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::N => Direction::S,
+            Direction::E => Direction::W,
+            Direction::S => Direction::N,
+            Direction::W => Direction::E,
+            Direction::U => Direction::D,
+            Direction::D => Direction::U,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -102,6 +238,9 @@ pub struct Entity {
     pub life: u32,
     #[serde(rename = "totalvie")]
     pub total_life: u32,
+    #[serde(rename = "niveau")]
+    pub level: u32,
+    pub xp: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -112,11 +251,138 @@ pub enum EntityType {
     Player,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Credentials {
+    #[serde(rename = "nom")]
+    pub username: String,
+    #[serde(rename = "motdepasse")]
+    pub password: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Status {
     pub guid: String,
+    #[serde(rename = "jeton")]
+    pub token: String,
     #[serde(rename = "totalvie")]
     pub total_life: u32,
+    #[serde(rename = "niveau")]
+    pub level: u32,
+    pub xp: u32,
     #[serde(rename = "salle")]
     pub room: Room,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerSummary {
+    pub guid: String,
+    #[serde(rename = "nom")]
+    pub username: Option<String>,
+    #[serde(rename = "niveau")]
+    pub level: u32,
+    #[serde(rename = "vie")]
+    pub life: u32,
+    #[serde(rename = "totalvie")]
+    pub total_life: u32,
+    pub x: isize,
+    pub y: isize,
+    pub z: isize,
+    #[serde(rename = "inactif")]
+    pub idle_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ban {
+    pub ip: String,
+    #[serde(default, rename = "expiration")]
+    pub expires: Option<u64>,
+    #[serde(default, rename = "raison")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Announcement {
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum RoomEvent {
+    #[serde(rename = "CONNEXION")]
+    Connected { guid: String },
+    #[serde(rename = "DECONNEXION")]
+    Disconnected { guid: String },
+    #[serde(rename = "ARRIVEE")]
+    Entered { guid: String },
+    #[serde(rename = "DEPART")]
+    Left { guid: String, direction: Direction },
+    #[serde(rename = "COMBAT")]
+    Attacked {
+        #[serde(rename = "combat")]
+        fight: Fight,
+    },
+    #[serde(rename = "MORT")]
+    Died { guid: String },
+    #[serde(rename = "APPARITION")]
+    Spawned { guid: String },
+    #[serde(rename = "NIVEAU")]
+    LevelUp {
+        guid: String,
+        #[serde(rename = "niveau")]
+        level: u32,
+    },
+    #[serde(rename = "ANNONCE")]
+    Announced { message: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_their_status_and_kind() {
+        let cases = vec![
+            (WorldError::Disappeared, 410, "MORT"),
+            (WorldError::Wall, 409, "MUR"),
+            (
+                WorldError::Locked(i18n::Message::LockedUntilCleared),
+                409,
+                "VERROUILLE",
+            ),
+            (WorldError::DiffRoom, 409, "DIFFSALLE"),
+            (WorldError::Unauthorized, 401, "NONAUTORISE"),
+            (WorldError::BadCredentials, 401, "IDENTIFIANTS"),
+            (
+                WorldError::EntityNotFound(String::from("guid")),
+                404,
+                "ENTITEINTROUVABLE",
+            ),
+            (
+                WorldError::ItemNotFound(String::from("guid")),
+                404,
+                "OBJETINTROUVABLE",
+            ),
+            (WorldError::RateLimited, 429, "TROPRAPIDE"),
+            (WorldError::TooManyStreams, 503, "TROPDEFLUX"),
+            (WorldError::Banned(None), 403, "BANNI"),
+            (
+                WorldError::BadRequest(i18n::Message::InvalidUsername(32)),
+                400,
+                "REQUETEINVALIDE",
+            ),
+            (WorldError::Unprocessable, 422, "CORPSINVALIDE"),
+            (WorldError::NotFound, 404, "INTROUVABLE"),
+            (WorldError::Internal, 500, "ERREURINTERNE"),
+            (
+                WorldError::Other(i18n::Message::UsernameTaken),
+                409,
+                "AUTRE",
+            ),
+        ];
+
+        for (error, code, kind) in cases.into_iter() {
+            assert_eq!(error.status().code, code, "{:?}", error);
+            assert_eq!(error.kind(), kind, "{:?}", error);
+        }
+    }
+}
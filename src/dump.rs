@@ -1,37 +1,813 @@
+use crate::data_model;
 use crate::gen_plan;
+use crate::i18n;
 
-use image;
+use std::convert::TryFrom;
+use std::error;
+use std::fs;
+use std::io;
+use std::str::FromStr;
 
-pub fn dump_world(world_plan: &gen_plan::WorldPlan) {
-    let width = world_plan.get_width();
-    let height = world_plan.get_height();
+const AVERAGE_RANDOM_MONSTER_STRENGTH: f32 = 89.5 * 19.5;
+const LEGEND_SCALE: u32 = 2;
+const LEGEND_SWATCH: u32 = 10;
+const LEGEND_GRADIENT_WIDTH: u32 = 40;
+const LEGEND_PADDING: u32 = 4;
+const MAX_IMAGE_SIDE: u32 = 16384;
+
+pub const MAX_TILE_SIZE: u32 = 64;
+
+static BACKGROUND: image::Rgb<u8> = image::Rgb([0, 0, 0]);
+static SPAWN: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+static STAIRS: image::Rgb<u8> = image::Rgb([255, 220, 0]);
+static REGEN: image::Rgb<u8> = image::Rgb([0, 200, 255]);
+static MONSTERS: image::Rgb<u8> = image::Rgb([30, 30, 30]);
+static TEXT: image::Rgb<u8> = image::Rgb([220, 220, 220]);
+static EMPTY: image::Rgb<u8> = image::Rgb([90, 90, 90]);
+static CLEARED: image::Rgb<u8> = image::Rgb([40, 120, 40]);
+static PLAYERS: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+
+pub struct LiveTile {
+    pub x: isize,
+    pub y: isize,
+    pub players: usize,
+    pub monsters: usize,
+    pub cleared: bool,
+    pub stairs: bool,
+    pub spawn: bool,
+}
+
+static UNKNOWN_FORMAT_ERROR: &str = "Unknown dump format";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Svg,
+    Ascii,
+    Unicode,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Format, String> {
+        match format {
+            "png" => Ok(Format::Png),
+            "svg" => Ok(Format::Svg),
+            "ascii" => Ok(Format::Ascii),
+            "unicode" => Ok(Format::Unicode),
+            _ => Err(format!("{} {}", UNKNOWN_FORMAT_ERROR, format)),
+        }
+    }
+}
+
+pub struct DumpOptions {
+    pub format: Format,
+    pub tile_size: u32,
+    pub output: Option<String>,
+    pub legend: bool,
+}
+
+struct Charset {
+    room: char,
+    spawn: char,
+    regen: char,
+    up: char,
+    down: char,
+    both: char,
+    horizontal: char,
+    vertical: char,
+}
+
+static ASCII: Charset = Charset {
+    room: '#',
+    spawn: '@',
+    regen: '+',
+    up: '<',
+    down: '>',
+    both: 'X',
+    horizontal: '-',
+    vertical: '|',
+};
+
+static UNICODE: Charset = Charset {
+    room: '■',
+    spawn: '★',
+    regen: '✚',
+    up: '▲',
+    down: '▼',
+    both: '◆',
+    horizontal: '─',
+    vertical: '│',
+};
+
+fn monster_count(room: &gen_plan::RoomPlan) -> usize {
+    match &room.monsters {
+        Some(gen_plan::MonstersPlan::Random(length)) => *length,
+        Some(gen_plan::MonstersPlan::Defined(monsters)) => monsters.len(),
+        None => 0,
+    }
+}
+
+fn danger(room: &gen_plan::RoomPlan) -> f32 {
+    match &room.monsters {
+        Some(gen_plan::MonstersPlan::Random(length)) => {
+            *length as f32 * AVERAGE_RANDOM_MONSTER_STRENGTH
+        }
+        Some(gen_plan::MonstersPlan::Defined(monsters)) => monsters
+            .iter()
+            .map(|monster| monster.hp as f32 * monster.dp as f32)
+            .sum(),
+        None => 0f32,
+    }
+}
+
+fn lerp(from: [u8; 3], to: [u8; 3], t: f32) -> image::Rgb<u8> {
+    let mut channels = [0u8; 3];
+
+    for i in 0..3 {
+        channels[i] = (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8;
+    }
+
+    image::Rgb(channels)
+}
+
+fn gradient(t: f32) -> image::Rgb<u8> {
+    let t = t.max(0f32).min(1f32);
+
+    if t < 0.5 {
+        lerp([40, 160, 40], [230, 220, 40], t * 2f32)
+    } else {
+        lerp([230, 220, 40], [220, 30, 30], (t - 0.5) * 2f32)
+    }
+}
+
+fn fill(
+    img_buf: &mut image::RgbImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    colour: image::Rgb<u8>,
+) {
+    for py in y..std::cmp::min(y + height, img_buf.height()) {
+        for px in x..std::cmp::min(x + width, img_buf.width()) {
+            img_buf.put_pixel(px, py, colour);
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b111, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        _ => [0; 5],
+    }
+}
+
+fn draw_text(img_buf: &mut image::RgbImage, x: u32, y: u32, text: &str) {
+    for (index, c) in text.chars().enumerate() {
+        let glyph_x = x + index as u32 * 4 * LEGEND_SCALE;
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    fill(
+                        img_buf,
+                        glyph_x + column * LEGEND_SCALE,
+                        y + row as u32 * LEGEND_SCALE,
+                        LEGEND_SCALE,
+                        LEGEND_SCALE,
+                        TEXT,
+                    );
+                }
+            }
+        }
+    }
+}
+
+static LEGEND_ENTRIES: [&str; 5] = ["DIFFICULTY", "SPAWN", "STAIRS", "REGEN", "MONSTERS"];
+
+fn legend_size() -> (u32, u32) {
+    let longest = LEGEND_ENTRIES
+        .iter()
+        .map(|entry| entry.len())
+        .max()
+        .unwrap_or(0) as u32;
+    let line_height = LEGEND_SWATCH + LEGEND_PADDING;
+
+    (
+        LEGEND_PADDING * 3 + LEGEND_GRADIENT_WIDTH + longest * 4 * LEGEND_SCALE,
+        LEGEND_PADDING + LEGEND_ENTRIES.len() as u32 * line_height,
+    )
+}
+
+fn draw_legend(img_buf: &mut image::RgbImage, top: u32) {
+    let line_height = LEGEND_SWATCH + LEGEND_PADDING;
+    let label_x = LEGEND_PADDING * 2 + LEGEND_GRADIENT_WIDTH;
+
+    for (index, label) in LEGEND_ENTRIES.iter().enumerate() {
+        let y = top + LEGEND_PADDING + index as u32 * line_height;
+
+        match index {
+            0 => {
+                for x in 0..LEGEND_GRADIENT_WIDTH {
+                    let colour = gradient(x as f32 / (LEGEND_GRADIENT_WIDTH - 1) as f32);
+                    fill(img_buf, LEGEND_PADDING + x, y, 1, LEGEND_SWATCH, colour);
+                }
+            }
+            1 => fill(
+                img_buf,
+                LEGEND_PADDING,
+                y,
+                LEGEND_SWATCH,
+                LEGEND_SWATCH,
+                SPAWN,
+            ),
+            2 => fill(
+                img_buf,
+                LEGEND_PADDING,
+                y,
+                LEGEND_SWATCH,
+                LEGEND_SWATCH,
+                STAIRS,
+            ),
+            3 => fill(
+                img_buf,
+                LEGEND_PADDING,
+                y,
+                LEGEND_SWATCH,
+                LEGEND_SWATCH,
+                REGEN,
+            ),
+            _ => {
+                fill(
+                    img_buf,
+                    LEGEND_PADDING,
+                    y,
+                    LEGEND_SWATCH,
+                    LEGEND_SWATCH,
+                    gradient(0f32),
+                );
+                fill(img_buf, LEGEND_PADDING + 3, y + 3, 4, 4, MONSTERS);
+            }
+        }
+
+        draw_text(
+            img_buf,
+            label_x,
+            y + (LEGEND_SWATCH - 5 * LEGEND_SCALE) / 2,
+            label,
+        );
+    }
+}
+
+fn level_path(output: &str, level: isize) -> String {
+    match output.rfind('.') {
+        Some(index) => format!("{}_{}{}", &output[..index], level, &output[index..]),
+        None => format!("{}_{}", output, level),
+    }
+}
+
+fn is_spawn(world_plan: &gen_plan::WorldPlan, room: &gen_plan::RoomPlan) -> bool {
+    room.x == world_plan.spawn_x && room.y == world_plan.spawn_y && room.z == world_plan.spawn_z
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn bounds(world_plan: &gen_plan::WorldPlan) -> (isize, isize, isize, isize) {
+    let min_x = world_plan
+        .rooms
+        .iter()
+        .map(|room| room.x)
+        .min()
+        .unwrap_or(0);
+    let max_x = world_plan
+        .rooms
+        .iter()
+        .map(|room| room.x)
+        .max()
+        .unwrap_or(0);
+    let min_y = world_plan
+        .rooms
+        .iter()
+        .map(|room| room.y)
+        .min()
+        .unwrap_or(0);
+    let max_y = world_plan
+        .rooms
+        .iter()
+        .map(|room| room.y)
+        .max()
+        .unwrap_or(0);
+
+    (min_x, max_x, min_y, max_y)
+}
+
+fn write_output(path: &str, data: String) -> io::Result<()> {
+    fs::write(path, data)?;
+
+    println!("Dumped map to {}", path);
+
+    Ok(())
+}
+
+fn dump_svg(
+    world_plan: &gen_plan::WorldPlan,
+    options: &DumpOptions,
+    output: &str,
+) -> io::Result<()> {
+    let cell = options.tile_size.clamp(4, MAX_TILE_SIZE) as f32;
+    let (min_x, max_x, min_y, max_y) = bounds(world_plan);
+    let width = (max_x - min_x + 1) as f32 * cell;
+    let height = (max_y - min_y + 1) as f32 * cell;
+
+    let positions = world_plan.positions();
+    let max_danger = world_plan.rooms.iter().map(danger).fold(0f32, f32::max);
+    let levels = world_plan.get_levels();
+
+    for level in levels.iter() {
+        let centre = |room: &gen_plan::RoomPlan| {
+            (
+                (room.x - min_x) as f32 * cell + cell / 2f32,
+                (max_y - room.y) as f32 * cell + cell / 2f32,
+            )
+        };
+        let rooms: Vec<&gen_plan::RoomPlan> = world_plan
+            .rooms
+            .iter()
+            .filter(|room| room.z == *level)
+            .collect();
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n<rect width=\"{0}\" height=\"{1}\" fill=\"{2}\"/>\n<g stroke=\"{3}\" stroke-width=\"{4}\">\n",
+            width,
+            height,
+            hex(BACKGROUND),
+            hex(EMPTY),
+            cell * 0.3
+        );
+
+        for room in rooms.iter() {
+            let (x, y) = centre(room);
+
+            for (direction, dx, dy) in [
+                (data_model::Direction::E, cell, 0f32),
+                (data_model::Direction::N, 0f32, -cell),
+            ]
+            .iter()
+            {
+                if world_plan.connects(&positions, room, direction) {
+                    svg.push_str(&format!(
+                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>\n",
+                        x,
+                        y,
+                        x + dx,
+                        y + dy
+                    ));
+                }
+            }
+        }
+
+        svg.push_str("</g>\n");
+
+        for room in rooms.iter() {
+            let (x, y) = centre(room);
+            let size = cell * 0.7;
+            let fill = if is_spawn(world_plan, room) {
+                SPAWN
+            } else if max_danger > 0f32 {
+                gradient(danger(room) / max_danger)
+            } else {
+                gradient(0f32)
+            };
+            let stroke = match (room.stairs, room.hp_regen.unwrap_or(0) > 0) {
+                (Some(_), _) => format!(
+                    " stroke=\"{}\" stroke-width=\"{}\"",
+                    hex(STAIRS),
+                    cell * 0.1
+                ),
+                (None, true) => {
+                    format!(" stroke=\"{}\" stroke-width=\"{}\"", hex(REGEN), cell * 0.1)
+                }
+                (None, false) => String::new(),
+            };
+
+            let description = match &room.description {
+                Some(description) => description.get(i18n::Lang::default()),
+                None => i18n::Message::DefaultRoom.translate(i18n::Lang::default()),
+            };
+
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{size}\" height=\"{size}\" fill=\"{}\"{}><title>({},{}) {}</title></rect>\n",
+                x - size / 2f32,
+                y - size / 2f32,
+                hex(fill),
+                stroke,
+                room.x,
+                room.y,
+                escape_xml(description.as_str()),
+                size = size
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+
+        let path = if levels.len() > 1 {
+            level_path(output, *level)
+        } else {
+            output.to_string()
+        };
+
+        write_output(&path, svg)?;
+    }
+
+    Ok(())
+}
+
+fn render_text(world_plan: &gen_plan::WorldPlan, charset: &Charset) -> String {
+    let (min_x, max_x, min_y, max_y) = bounds(world_plan);
+    let width = ((max_x - min_x) * 2 + 1) as usize;
+    let height = ((max_y - min_y) * 2 + 1) as usize;
+
+    let positions = world_plan.positions();
+    let levels = world_plan.get_levels();
+    let mut text = String::new();
+
+    for level in levels.iter() {
+        let mut grid = vec![vec![' '; width]; height];
+
+        for room in world_plan.rooms.iter().filter(|room| room.z == *level) {
+            let column = ((room.x - min_x) * 2) as usize;
+            let row = ((max_y - room.y) * 2) as usize;
+
+            grid[row][column] = if is_spawn(world_plan, room) {
+                charset.spawn
+            } else {
+                match room.stairs {
+                    Some(gen_plan::Stairs::Up) => charset.up,
+                    Some(gen_plan::Stairs::Down) => charset.down,
+                    Some(gen_plan::Stairs::Both) => charset.both,
+                    None if room.hp_regen.unwrap_or(0) > 0 => charset.regen,
+                    None => charset.room,
+                }
+            };
+
+            if world_plan.connects(&positions, room, &data_model::Direction::E) {
+                grid[row][column + 1] = charset.horizontal;
+            }
+
+            if world_plan.connects(&positions, room, &data_model::Direction::N) {
+                grid[row - 1][column] = charset.vertical;
+            }
+        }
+
+        if levels.len() > 1 {
+            text.push_str(&format!("Floor {}\n", level));
+        }
+
+        for line in grid.iter() {
+            let line: String = line.iter().collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+pub fn dump_world(
+    world_plan: &gen_plan::WorldPlan,
+    options: &DumpOptions,
+) -> Result<(), Box<dyn error::Error>> {
+    match options.format {
+        Format::Png => dump_png(
+            world_plan,
+            options,
+            options.output.as_deref().unwrap_or("world_dump.png"),
+        )?,
+        Format::Svg => dump_svg(
+            world_plan,
+            options,
+            options.output.as_deref().unwrap_or("world_dump.svg"),
+        )?,
+        Format::Ascii | Format::Unicode => {
+            let charset = if options.format == Format::Ascii {
+                &ASCII
+            } else {
+                &UNICODE
+            };
+            let text = render_text(world_plan, charset);
+
+            match &options.output {
+                Some(path) => write_output(path, text)?,
+                None => io::Write::write_all(&mut io::stdout(), text.as_bytes())?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Size in pixels of a side of `tiles` rooms, refused when the image would be
+// too large to allocate.
+fn image_side(tiles: usize, tile_size: u32) -> Result<u32, image::ImageError> {
+    u32::try_from(tiles)
+        .ok()
+        .and_then(|tiles| tiles.checked_mul(tile_size))
+        .filter(|side| *side <= MAX_IMAGE_SIDE)
+        .ok_or_else(|| {
+            image::ImageError::Limits(image::error::LimitError::from_kind(
+                image::error::LimitErrorKind::DimensionError,
+            ))
+        })
+}
+
+fn dump_png(
+    world_plan: &gen_plan::WorldPlan,
+    options: &DumpOptions,
+    output: &str,
+) -> Result<(), image::ImageError> {
+    let tile_size = options.tile_size.clamp(1, MAX_TILE_SIZE);
+
+    let width = image_side(world_plan.get_width(), tile_size)?;
+    let height = image_side(world_plan.get_height(), tile_size)?;
 
     let x_offset = world_plan.get_x_offset();
     let y_offset = world_plan.get_y_offset();
+    let last_row = world_plan.get_height() - 1;
+
+    let max_danger = world_plan.rooms.iter().map(danger).fold(0f32, f32::max);
+    let max_monsters = world_plan
+        .rooms
+        .iter()
+        .map(monster_count)
+        .max()
+        .unwrap_or(0);
 
-    let mut img_buf = image::ImageBuffer::new(width as u32, height as u32);
+    let (legend_width, legend_height) = if options.legend {
+        legend_size()
+    } else {
+        (0, 0)
+    };
 
-    for (_, _, pixel) in img_buf.enumerate_pixels_mut() {
-        *pixel = image::Rgb([0u8, 0u8, 0u8]);
+    let levels = world_plan.get_levels();
+
+    for level in levels.iter() {
+        let mut img_buf = image::ImageBuffer::from_pixel(
+            std::cmp::max(width, legend_width),
+            height + legend_height,
+            BACKGROUND,
+        );
+
+        for room in world_plan.rooms.iter().filter(|room| room.z == *level) {
+            let x = (room.x + x_offset as isize) as u32 * tile_size;
+            // North is up, like in the SVG and text maps.
+            let y = (last_row - (room.y + y_offset as isize) as usize) as u32 * tile_size;
+
+            if room.x == world_plan.spawn_x
+                && room.y == world_plan.spawn_y
+                && room.z == world_plan.spawn_z
+            {
+                fill(&mut img_buf, x, y, tile_size, tile_size, SPAWN);
+                continue;
+            }
+
+            let difficulty = if max_danger > 0f32 {
+                danger(room) / max_danger
+            } else {
+                0f32
+            };
+            fill(
+                &mut img_buf,
+                x,
+                y,
+                tile_size,
+                tile_size,
+                gradient(difficulty),
+            );
+
+            if tile_size < 3 {
+                if room.stairs.is_some() {
+                    fill(&mut img_buf, x, y, tile_size, tile_size, STAIRS);
+                }
+                continue;
+            }
+
+            let marker_size = std::cmp::max(tile_size / 3, 1);
+
+            if room.hp_regen.unwrap_or(0) > 0 {
+                fill(&mut img_buf, x, y, marker_size, marker_size, REGEN);
+            }
+
+            if room.stairs.is_some() {
+                fill(
+                    &mut img_buf,
+                    x + tile_size - marker_size,
+                    y + tile_size - marker_size,
+                    marker_size,
+                    marker_size,
+                    STAIRS,
+                );
+            }
+
+            let monsters = monster_count(room);
+
+            if monsters > 0 {
+                let density = (monsters as f32 / max_monsters as f32).sqrt();
+                let dot_size = std::cmp::max((density * (tile_size - 2) as f32).round() as u32, 1);
+                let dot_offset = (tile_size - dot_size) / 2;
+
+                fill(
+                    &mut img_buf,
+                    x + dot_offset,
+                    y + dot_offset,
+                    dot_size,
+                    dot_size,
+                    MONSTERS,
+                );
+            }
+        }
+
+        if options.legend {
+            draw_legend(&mut img_buf, height);
+        }
+
+        let path = if levels.len() > 1 {
+            level_path(output, *level)
+        } else {
+            output.to_string()
+        };
+
+        img_buf.save(&path)?;
+
+        println!("Dumped map to {}", path);
     }
 
-    for room in &world_plan.rooms {
-        let x = (room.x + x_offset as isize) as u32;
-        let y = (room.y + y_offset as isize) as u32;
+    Ok(())
+}
+
+struct LiveLayout {
+    min_x: isize,
+    max_y: isize,
+    width: u32,
+    height: u32,
+    max_monsters: usize,
+}
+
+impl LiveLayout {
+    fn new(tiles: &[LiveTile], tile_size: u32) -> Result<LiveLayout, image::ImageError> {
+        let min_x = tiles.iter().map(|tile| tile.x).min().unwrap_or(0);
+        let max_x = tiles.iter().map(|tile| tile.x).max().unwrap_or(0);
+        let min_y = tiles.iter().map(|tile| tile.y).min().unwrap_or(0);
+        let max_y = tiles.iter().map(|tile| tile.y).max().unwrap_or(0);
 
-        let pixel = img_buf.get_pixel_mut(x, y);
-        if room.x == world_plan.spawn_x && room.y == world_plan.spawn_y {
-            *pixel = image::Rgb([255u8, 0u8, 0u8]);
+        Ok(LiveLayout {
+            min_x,
+            max_y,
+            width: image_side((max_x - min_x + 1) as usize, tile_size)?,
+            height: image_side((max_y - min_y + 1) as usize, tile_size)?,
+            max_monsters: tiles.iter().map(|tile| tile.monsters).max().unwrap_or(0),
+        })
+    }
+
+    fn position(&self, tile: &LiveTile, tile_size: u32) -> (u32, u32) {
+        (
+            (tile.x - self.min_x) as u32 * tile_size,
+            (self.max_y - tile.y) as u32 * tile_size,
+        )
+    }
+
+    fn colour(&self, tile: &LiveTile) -> image::Rgb<u8> {
+        if tile.monsters > 0 {
+            gradient(0.5 + 0.5 * tile.monsters as f32 / self.max_monsters as f32)
+        } else if tile.cleared {
+            CLEARED
         } else {
-            if x % 2 == y % 2 {
-                *pixel = image::Rgb([50u8, 255u8, 50u8]);
-            } else {
-                *pixel = image::Rgb([50u8, 200u8, 50u8]);
+            EMPTY
+        }
+    }
+}
+
+fn hex(colour: image::Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour[0], colour[1], colour[2])
+}
+
+pub fn render_live_png(tiles: &[LiveTile], tile_size: u32) -> Result<Vec<u8>, image::ImageError> {
+    let tile_size = std::cmp::max(tile_size, 1);
+    let layout = LiveLayout::new(tiles, tile_size)?;
+    let mut img_buf = image::ImageBuffer::from_pixel(layout.width, layout.height, BACKGROUND);
+    let marker_size = std::cmp::max(tile_size / 3, 1);
+
+    for tile in tiles.iter() {
+        let (x, y) = layout.position(tile, tile_size);
+
+        fill(
+            &mut img_buf,
+            x,
+            y,
+            tile_size,
+            tile_size,
+            layout.colour(tile),
+        );
+
+        if tile_size >= 3 {
+            if tile.spawn {
+                fill(&mut img_buf, x, y, marker_size, marker_size, SPAWN);
             }
+
+            if tile.stairs {
+                fill(
+                    &mut img_buf,
+                    x + tile_size - marker_size,
+                    y + tile_size - marker_size,
+                    marker_size,
+                    marker_size,
+                    STAIRS,
+                );
+            }
+        }
+
+        if tile.players > 0 {
+            let dot_size = std::cmp::max(tile_size / 2, 1);
+            let dot_offset = (tile_size - dot_size) / 2;
+
+            fill(
+                &mut img_buf,
+                x + dot_offset,
+                y + dot_offset,
+                dot_size,
+                dot_size,
+                PLAYERS,
+            );
+        }
+    }
+
+    let mut data = Vec::new();
+    image::DynamicImage::ImageRgb8(img_buf).write_to(&mut data, image::ImageOutputFormat::Png)?;
+
+    Ok(data)
+}
+
+pub fn render_live_svg(tiles: &[LiveTile], tile_size: u32) -> Result<String, image::ImageError> {
+    let tile_size = std::cmp::max(tile_size, 1);
+    let layout = LiveLayout::new(tiles, tile_size)?;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n<rect width=\"{0}\" height=\"{1}\" fill=\"{2}\"/>\n",
+        layout.width,
+        layout.height,
+        hex(BACKGROUND)
+    );
+
+    for tile in tiles.iter() {
+        let (x, y) = layout.position(tile, tile_size);
+
+        svg.push_str(&format!(
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{size}\" height=\"{size}\" fill=\"{fill}\"><title>({room_x},{room_y}) {players} players, {monsters} monsters{cleared}</title></rect>\n",
+            x = x,
+            y = y,
+            size = tile_size,
+            fill = hex(layout.colour(tile)),
+            room_x = tile.x,
+            room_y = tile.y,
+            players = tile.players,
+            monsters = tile.monsters,
+            cleared = if tile.cleared { ", cleared" } else { "" }
+        ));
+
+        if tile.players > 0 {
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+                x as f32 + tile_size as f32 / 2f32,
+                y as f32 + tile_size as f32 / 2f32,
+                tile_size as f32 / 4f32,
+                hex(PLAYERS)
+            ));
         }
     }
 
-    img_buf.save("world_dump.png").unwrap();
+    svg.push_str("</svg>\n");
 
-    println!("Dumped map");
+    Ok(svg)
 }
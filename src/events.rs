@@ -0,0 +1,188 @@
+use crate::data_model;
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time;
+
+static KEEP_ALIVE: &[u8] = b": keep-alive\n\n";
+
+#[derive(Clone, Debug, Default)]
+pub struct Subscribers {
+    senders: HashMap<String, mpsc::Sender<data_model::RoomEvent>>,
+}
+
+impl Subscribers {
+    // A player only gets one stream: opening a new one closes the previous.
+    pub fn subscribe(&mut self, guid: String) -> mpsc::Receiver<data_model::RoomEvent> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.senders.insert(guid, sender);
+
+        receiver
+    }
+
+    pub fn unsubscribe(&mut self, guid: &str) {
+        self.senders.remove(guid);
+    }
+
+    pub fn notify(&mut self, guids: &[String], event: &data_model::RoomEvent) {
+        for guid in guids.iter() {
+            self.send(guid, event);
+        }
+    }
+
+    // Returns whether an open stream received the event.
+    pub fn send(&mut self, guid: &str, event: &data_model::RoomEvent) -> bool {
+        let sent = match self.senders.get(guid) {
+            Some(sender) => sender.send(event.clone()).is_ok(),
+            None => return false,
+        };
+
+        if !sent {
+            self.senders.remove(guid);
+        }
+
+        sent
+    }
+}
+
+// Every open stream holds a Rocket worker, so their number is capped to keep
+// workers free for the other routes.
+#[derive(Debug)]
+pub struct StreamSlots {
+    open: Arc<AtomicUsize>,
+    max: usize,
+}
+
+pub struct StreamSlot {
+    open: Arc<AtomicUsize>,
+}
+
+impl StreamSlots {
+    pub fn new(max: usize) -> StreamSlots {
+        StreamSlots {
+            open: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    pub fn acquire(&self) -> Option<StreamSlot> {
+        let max = self.max;
+
+        self.open
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                if open < max {
+                    Some(open + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()?;
+
+        Some(StreamSlot {
+            open: Arc::clone(&self.open),
+        })
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct EventStream {
+    receiver: mpsc::Receiver<data_model::RoomEvent>,
+    _slot: StreamSlot,
+    pending: Vec<u8>,
+    flush: bool,
+    keep_alive_rate: time::Duration,
+}
+
+impl EventStream {
+    pub fn new(receiver: mpsc::Receiver<data_model::RoomEvent>, slot: StreamSlot) -> EventStream {
+        EventStream {
+            receiver,
+            _slot: slot,
+            pending: Vec::new(),
+            flush: false,
+            keep_alive_rate: time::Duration::from_secs(15),
+        }
+    }
+
+    fn next_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.receiver.recv_timeout(self.keep_alive_rate) {
+            Ok(event) => Ok(Some(
+                format!("data: {}\n\n", serde_json::to_string(&event)?).into_bytes(),
+            )),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(Some(KEEP_ALIVE.to_vec())),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Ok(None),
+        }
+    }
+}
+
+impl Read for EventStream {
+    // Rocket flushes a chunked body when the reader yields `WouldBlock`, so
+    // one is raised after every complete message to push it to the client.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            if self.flush {
+                self.flush = false;
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "flush event"));
+            }
+
+            match self.next_message()? {
+                Some(message) => self.pending = message,
+                None => return Ok(0),
+            }
+        }
+
+        let length = std::cmp::min(buf.len(), self.pending.len());
+        buf[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+
+        // A filled buffer ends Rocket's read loop on its own, and a
+        // `WouldBlock` at the start of the next loop would end the stream.
+        self.flush = self.pending.is_empty() && length < buf.len();
+
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_new_stream_closes_the_previous_one() {
+        let mut subscribers = Subscribers::default();
+        let first = subscribers.subscribe(String::from("player"));
+        let second = subscribers.subscribe(String::from("player"));
+
+        assert!(subscribers.send(
+            "player",
+            &data_model::RoomEvent::Announced {
+                message: String::from("Hello"),
+            }
+        ));
+        assert_eq!(
+            first.try_recv().unwrap_err(),
+            mpsc::TryRecvError::Disconnected
+        );
+        assert!(second.try_recv().is_ok());
+    }
+
+    #[test]
+    fn stream_slots_are_capped_and_released() {
+        let slots = StreamSlots::new(2);
+        let first = slots.acquire().unwrap();
+        let _second = slots.acquire().unwrap();
+
+        assert!(slots.acquire().is_none());
+
+        drop(first);
+        assert!(slots.acquire().is_some());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::gen_plan;
+use crate::seed;
+
+use std::collections::HashSet;
+
+const MIN_MAP_SIZE: u32 = 60;
+const MAX_MAP_SIZE: u32 = 160;
+const MIN_LEAF_SIZE: isize = 10;
+const MIN_CHAMBER_SIZE: isize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: isize,
+    y: isize,
+    width: isize,
+    height: isize,
+}
+
+impl Rect {
+    fn center(&self) -> (isize, isize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    fn split(&self, seeder: &mut seed::Seeder) -> Option<(Rect, Rect)> {
+        let horizontal = if self.width * 4 > self.height * 5 {
+            false
+        } else if self.height * 4 > self.width * 5 {
+            true
+        } else {
+            seeder.seed_u32_bounded(0, 1) == 0
+        };
+
+        let size = if horizontal { self.height } else { self.width };
+
+        if size < MIN_LEAF_SIZE * 2 {
+            return None;
+        }
+
+        let at =
+            seeder.seed_u32_bounded(MIN_LEAF_SIZE as u32, (size - MIN_LEAF_SIZE) as u32) as isize;
+
+        if horizontal {
+            Some((
+                Rect {
+                    height: at,
+                    ..*self
+                },
+                Rect {
+                    y: self.y + at,
+                    height: self.height - at,
+                    ..*self
+                },
+            ))
+        } else {
+            Some((
+                Rect { width: at, ..*self },
+                Rect {
+                    x: self.x + at,
+                    width: self.width - at,
+                    ..*self
+                },
+            ))
+        }
+    }
+}
+
+struct Carver {
+    tiles: Vec<(isize, isize)>,
+    carved: HashSet<(isize, isize)>,
+    chambers: Vec<(isize, isize)>,
+}
+
+impl Carver {
+    fn new() -> Carver {
+        Carver {
+            tiles: Vec::new(),
+            carved: HashSet::new(),
+            chambers: Vec::new(),
+        }
+    }
+
+    fn carve(&mut self, coords: (isize, isize)) {
+        if self.carved.insert(coords) {
+            self.tiles.push(coords);
+        }
+    }
+
+    fn carve_chamber(&mut self, seeder: &mut seed::Seeder, leaf: Rect) -> (isize, isize) {
+        let width =
+            seeder.seed_u32_bounded(MIN_CHAMBER_SIZE as u32, (leaf.width - 2) as u32) as isize;
+        let height =
+            seeder.seed_u32_bounded(MIN_CHAMBER_SIZE as u32, (leaf.height - 2) as u32) as isize;
+        let chamber = Rect {
+            x: leaf.x + 1 + seeder.seed_u32_bounded(0, (leaf.width - 2 - width) as u32) as isize,
+            y: leaf.y + 1 + seeder.seed_u32_bounded(0, (leaf.height - 2 - height) as u32) as isize,
+            width,
+            height,
+        };
+
+        for y in chamber.y..chamber.y + chamber.height {
+            for x in chamber.x..chamber.x + chamber.width {
+                self.carve((x, y));
+            }
+        }
+
+        self.chambers.push(chamber.center());
+
+        chamber.center()
+    }
+
+    fn carve_corridor(
+        &mut self,
+        seeder: &mut seed::Seeder,
+        from: (isize, isize),
+        to: (isize, isize),
+    ) {
+        let corner = if seeder.seed_u32_bounded(0, 1) == 0 {
+            (to.0, from.1)
+        } else {
+            (from.0, to.1)
+        };
+
+        for (start, end) in [(from, corner), (corner, to)].iter() {
+            let mut coords = *start;
+            self.carve(coords);
+
+            while coords != *end {
+                coords.0 += (end.0 - coords.0).signum();
+                coords.1 += (end.1 - coords.1).signum();
+                self.carve(coords);
+            }
+        }
+    }
+
+    fn partition(&mut self, seeder: &mut seed::Seeder, rect: Rect) -> (isize, isize) {
+        match rect.split(seeder) {
+            Some((first, second)) => {
+                let first_center = self.partition(seeder, first);
+                let second_center = self.partition(seeder, second);
+
+                self.carve_corridor(seeder, first_center, second_center);
+
+                if seeder.seed_u32_bounded(0, 1) == 0 {
+                    first_center
+                } else {
+                    second_center
+                }
+            }
+            None => self.carve_chamber(seeder, rect),
+        }
+    }
+}
+
+pub fn from_seeder(seeder: &mut seed::Seeder, floor: gen_plan::Floor) -> gen_plan::WorldPlan {
+    println!("Generating map");
+
+    let map = Rect {
+        x: 0,
+        y: 0,
+        width: seeder.seed_u32_bounded(MIN_MAP_SIZE, MAX_MAP_SIZE) as isize,
+        height: seeder.seed_u32_bounded(MIN_MAP_SIZE, MAX_MAP_SIZE) as isize,
+    };
+
+    let mut carver = Carver::new();
+    carver.partition(seeder, map);
+
+    println!("Generated {} chambers", carver.chambers.len());
+
+    let map_center = map.center();
+    let spawn = carver
+        .chambers
+        .iter()
+        .min_by_key(|chamber| (chamber.0 - map_center.0).pow(2) + (chamber.1 - map_center.1).pow(2))
+        .cloned()
+        .unwrap_or(map_center);
+
+    let mut world_plan = gen_plan::WorldPlan::new();
+    world_plan.rooms.push(gen_plan::RoomPlan::spawn(floor));
+
+    let room_count = (map.width * map.height) as u32;
+
+    for tile in carver.tiles.iter() {
+        let coords = (tile.0 - spawn.0, tile.1 - spawn.1);
+
+        if coords.0 != 0 || coords.1 != 0 {
+            world_plan.rooms.push(gen_plan::RoomPlan::from_seeder(
+                seeder, coords, room_count, floor,
+            ));
+        }
+    }
+
+    println!("Finished generating map");
+
+    world_plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate;
+
+    const FLOOR: gen_plan::Floor = gen_plan::Floor {
+        level: 0,
+        origin: (0, 0),
+    };
+
+    fn generate(seed: &str) -> gen_plan::WorldPlan {
+        from_seeder(&mut seed::Seeder::from_seed(String::from(seed)), FLOOR)
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_plan() {
+        assert_eq!(
+            serde_json::to_string(&generate("same")).unwrap(),
+            serde_json::to_string(&generate("same")).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_room_can_be_reached_from_the_spawn() {
+        for seed in ["first", "second", "third"].iter() {
+            let plan = generate(seed);
+            let issues = validate::validate(&plan, None);
+
+            assert!(plan
+                .rooms
+                .iter()
+                .any(|room| (room.x, room.y, room.z) == (plan.spawn_x, plan.spawn_y, 0)));
+            assert!(plan.rooms.len() > 1);
+            assert!(issues.is_empty(), "{}: {:?}", seed, issues);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::gen_plan;
+use crate::seed;
+
+use std::collections::VecDeque;
+
+const MIN_MAP_SIZE: u32 = 60;
+const MAX_MAP_SIZE: u32 = 140;
+const WALL_CHANCE: u32 = 45;
+const SMOOTHING_STEPS: usize = 5;
+const WALL_THRESHOLD: usize = 4;
+
+struct Grid {
+    width: usize,
+    height: usize,
+    walls: Vec<bool>,
+}
+
+impl Grid {
+    fn random(seeder: &mut seed::Seeder, width: usize, height: usize) -> Grid {
+        let mut walls = Vec::with_capacity(width * height);
+
+        for _ in 0..width * height {
+            walls.push(seeder.seed_u32_bounded(1, 100) <= WALL_CHANCE);
+        }
+
+        Grid {
+            width,
+            height,
+            walls,
+        }
+    }
+
+    fn is_wall(&self, x: isize, y: isize) -> bool {
+        if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+            true
+        } else {
+            self.walls[y as usize * self.width + x as usize]
+        }
+    }
+
+    fn wall_neighbours(&self, x: isize, y: isize) -> usize {
+        let mut count = 0;
+
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx != 0 || dy != 0) && self.is_wall(x + dx, y + dy) {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    fn smooth(&self) -> Grid {
+        let mut walls = Vec::with_capacity(self.walls.len());
+
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let neighbours = self.wall_neighbours(x, y);
+
+                walls.push(if neighbours == WALL_THRESHOLD {
+                    self.is_wall(x, y)
+                } else {
+                    neighbours > WALL_THRESHOLD
+                });
+            }
+        }
+
+        Grid {
+            width: self.width,
+            height: self.height,
+            walls,
+        }
+    }
+
+    fn regions(&self) -> Vec<Vec<(isize, isize)>> {
+        let mut visited = vec![false; self.walls.len()];
+        let mut regions = Vec::new();
+
+        for start in 0..self.walls.len() {
+            if self.walls[start] || visited[start] {
+                continue;
+            }
+
+            let mut region = Vec::new();
+            let mut queue = VecDeque::new();
+            visited[start] = true;
+            queue.push_back(((start % self.width) as isize, (start / self.width) as isize));
+
+            while let Some((x, y)) = queue.pop_front() {
+                region.push((x, y));
+
+                for (nx, ny) in [(x, y - 1), (x, y + 1), (x + 1, y), (x - 1, y)].iter() {
+                    if !self.is_wall(*nx, *ny) {
+                        let index = *ny as usize * self.width + *nx as usize;
+
+                        if !visited[index] {
+                            visited[index] = true;
+                            queue.push_back((*nx, *ny));
+                        }
+                    }
+                }
+            }
+
+            regions.push(region);
+        }
+
+        regions
+    }
+}
+
+pub fn from_seeder(seeder: &mut seed::Seeder, floor: gen_plan::Floor) -> gen_plan::WorldPlan {
+    println!("Generating map");
+
+    let width = seeder.seed_u32_bounded(MIN_MAP_SIZE, MAX_MAP_SIZE) as usize;
+    let height = seeder.seed_u32_bounded(MIN_MAP_SIZE, MAX_MAP_SIZE) as usize;
+    let mut grid = Grid::random(seeder, width, height);
+
+    for i in 0..SMOOTHING_STEPS {
+        grid = grid.smooth();
+        println!("Smoothed caves {}/{}", i + 1, SMOOTHING_STEPS);
+    }
+
+    let cave = grid
+        .regions()
+        .into_iter()
+        .max_by_key(|region| region.len())
+        .unwrap_or_else(|| vec![((width / 2) as isize, (height / 2) as isize)]);
+
+    println!("Kept a cave of {} rooms", cave.len());
+
+    let center = ((width / 2) as isize, (height / 2) as isize);
+    let spawn = cave
+        .iter()
+        .min_by_key(|tile| (tile.0 - center.0).pow(2) + (tile.1 - center.1).pow(2))
+        .cloned()
+        .unwrap_or(center);
+
+    let mut world_plan = gen_plan::WorldPlan::new();
+    world_plan.rooms.push(gen_plan::RoomPlan::spawn(floor));
+
+    let room_count = (width * height) as u32;
+
+    for tile in cave.iter() {
+        let coords = (tile.0 - spawn.0, tile.1 - spawn.1);
+
+        if coords.0 != 0 || coords.1 != 0 {
+            world_plan.rooms.push(gen_plan::RoomPlan::from_seeder(
+                seeder, coords, room_count, floor,
+            ));
+        }
+    }
+
+    println!("Finished generating map");
+
+    world_plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate;
+
+    const FLOOR: gen_plan::Floor = gen_plan::Floor {
+        level: 0,
+        origin: (0, 0),
+    };
+
+    fn generate(seed: &str) -> gen_plan::WorldPlan {
+        from_seeder(&mut seed::Seeder::from_seed(String::from(seed)), FLOOR)
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_plan() {
+        assert_eq!(
+            serde_json::to_string(&generate("same")).unwrap(),
+            serde_json::to_string(&generate("same")).unwrap()
+        );
+    }
+
+    #[test]
+    fn every_room_can_be_reached_from_the_spawn() {
+        for seed in ["first", "second", "third"].iter() {
+            let plan = generate(seed);
+            let issues = validate::validate(&plan, None);
+
+            assert!(plan
+                .rooms
+                .iter()
+                .any(|room| (room.x, room.y, room.z) == (plan.spawn_x, plan.spawn_y, 0)));
+            assert!(plan.rooms.len() > 1);
+            assert!(issues.is_empty(), "{}: {:?}", seed, issues);
+        }
+    }
+}
@@ -1,12 +1,193 @@
 use serde::{Deserialize, Serialize};
 
+use crate::data_model;
+use crate::gen_bsp;
+use crate::gen_cave;
+use crate::i18n;
 use crate::seed;
 
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+static UNKNOWN_STYLE_ERROR: &str = "Unknown generation style";
+
+const FLOOR_DIFFICULTY: u32 = 4;
+// Percentage added to the HP and DP of random monsters for every floor.
+const FLOOR_STAT_BONUS: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Corridors,
+    Bsp,
+    Caves,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Style, String> {
+        match style {
+            "corridors" => Ok(Style::Corridors),
+            "bsp" => Ok(Style::Bsp),
+            "caves" => Ok(Style::Caves),
+            _ => Err(format!("{} {}", UNKNOWN_STYLE_ERROR, style)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ItemKind {
+    Weapon { dp: u32 },
+    Armour { hp: u32 },
+    Potion { hp: u32 },
+    Key { key: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemPlan {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<i18n::Text>,
+    #[serde(flatten)]
+    pub kind: ItemKind,
+}
+
+impl ItemPlan {
+    pub fn random_loot(seeder: &mut seed::Seeder) -> Vec<ItemPlan> {
+        if seeder.seed_u32_bounded(0, 3) == 0 {
+            vec![ItemPlan {
+                description: None,
+                kind: ItemKind::Potion {
+                    hp: seeder.seed_u32_bounded(20, 49),
+                },
+            }]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Behaviour {
+    Static,
+    Wander,
+    Aggressive,
+    Flee,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Lock {
+    Key { key: String },
+    Cleared,
+    Level { level: u32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExitPlan {
+    pub direction: data_model::Direction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock: Option<Lock>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stairs {
+    Up,
+    Down,
+    Both,
+}
+
+impl Stairs {
+    pub fn goes_up(&self) -> bool {
+        *self != Stairs::Down
+    }
+
+    pub fn goes_down(&self) -> bool {
+        *self != Stairs::Up
+    }
+
+    fn with(self, other: Stairs) -> Stairs {
+        if self == other {
+            self
+        } else {
+            Stairs::Both
+        }
+    }
+}
+
+pub type Position = (isize, isize, isize);
+
+pub fn step(position: Position, direction: &data_model::Direction) -> Position {
+    let (x, y, z) = position;
+
+    match direction {
+        data_model::Direction::N => (x, y + 1, z),
+        data_model::Direction::S => (x, y - 1, z),
+        data_model::Direction::E => (x + 1, y, z),
+        data_model::Direction::W => (x - 1, y, z),
+        data_model::Direction::U => (x, y, z + 1),
+        data_model::Direction::D => (x, y, z - 1),
+    }
+}
+
+pub fn allows(
+    exits: &Option<Vec<ExitPlan>>,
+    stairs: &Option<Stairs>,
+    direction: &data_model::Direction,
+) -> bool {
+    match (direction, stairs) {
+        (data_model::Direction::U, Some(stairs)) => stairs.goes_up(),
+        (data_model::Direction::D, Some(stairs)) => stairs.goes_down(),
+        (data_model::Direction::U, None) | (data_model::Direction::D, None) => false,
+        _ => match exits {
+            Some(exits) => exits.iter().any(|exit| &exit.direction == direction),
+            None => true,
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Floor {
+    pub level: isize,
+    pub origin: (isize, isize),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DefinedMonsterPlan {
-    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<i18n::Text>,
     pub dp: u32,
     pub hp: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loot: Option<Vec<ItemPlan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behaviours: Option<Vec<Behaviour>>,
+}
+
+impl DefinedMonsterPlan {
+    pub fn random(seeder: &mut seed::Seeder) -> DefinedMonsterPlan {
+        let hp = seeder.seed_u32_bounded(30, 149);
+        let dp = seeder.seed_u32_bounded(10, 29);
+        let loot = ItemPlan::random_loot(seeder);
+
+        DefinedMonsterPlan {
+            description: Some(i18n::Message::SimpleMonster.into()),
+            dp,
+            hp,
+            loot: if loot.is_empty() { None } else { Some(loot) },
+            behaviours: None,
+        }
+    }
+
+    fn on_floor(mut self, level: isize) -> DefinedMonsterPlan {
+        let percent = 100 + level.max(0) as u32 * FLOOR_STAT_BONUS;
+
+        self.hp = self.hp * percent / 100;
+        self.dp = self.dp * percent / 100;
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -16,13 +197,102 @@ pub enum MonstersPlan {
     Random(usize),
 }
 
+impl MonstersPlan {
+    pub fn resolve(&self, seeder: &mut seed::Seeder) -> Vec<DefinedMonsterPlan> {
+        match self {
+            MonstersPlan::Random(length) => (0..*length)
+                .map(|_| DefinedMonsterPlan::random(seeder))
+                .collect(),
+            MonstersPlan::Defined(monster_plans) => monster_plans.clone(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RoomPlan {
     pub x: isize,
     pub y: isize,
-    pub description: Option<String>,
+    #[serde(default)]
+    pub z: isize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<i18n::Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub monsters: Option<MonstersPlan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub hp_regen: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ItemPlan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respawn_delay: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exits: Option<Vec<ExitPlan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stairs: Option<Stairs>,
+}
+
+impl RoomPlan {
+    pub fn spawn(floor: Floor) -> RoomPlan {
+        RoomPlan {
+            x: floor.origin.0,
+            y: floor.origin.1,
+            z: floor.level,
+            description: None,
+            monsters: None,
+            hp_regen: None,
+            items: None,
+            respawn_delay: None,
+            exits: None,
+            stairs: None,
+        }
+    }
+
+    pub fn from_seeder(
+        seeder: &mut seed::Seeder,
+        coords: (isize, isize),
+        room_count: u32,
+        floor: Floor,
+    ) -> RoomPlan {
+        let distance_multiplier = WorldPlan::get_difficulty_multiplier(room_count, coords);
+        let difficulty_multiplier = distance_multiplier + floor.level as u32 * FLOOR_DIFFICULTY;
+        let hp_regen = Some(seeder.seed_u32_bounded(0, 1) * difficulty_multiplier);
+        let x = coords.0 + floor.origin.0;
+        let y = coords.1 + floor.origin.1;
+
+        // The distance from spawn decides how many monsters there are and the
+        // floor how strong they are.
+        let monster_count = seeder.seed_u32_bounded(1, 3) as usize * distance_multiplier as usize;
+        let monsters = if floor.level > 0 {
+            MonstersPlan::Defined(
+                (0..monster_count)
+                    .map(|_| DefinedMonsterPlan::random(seeder).on_floor(floor.level))
+                    .collect(),
+            )
+        } else {
+            MonstersPlan::Random(monster_count)
+        };
+
+        RoomPlan {
+            x,
+            y,
+            z: floor.level,
+            description: Some(
+                i18n::Message::Room {
+                    x,
+                    y,
+                    level: floor.level,
+                    difficulty: difficulty_multiplier,
+                    hp_regen: hp_regen.unwrap_or(0),
+                }
+                .into(),
+            ),
+            monsters: Some(monsters),
+            hp_regen,
+            items: None,
+            respawn_delay: None,
+            exits: None,
+            stairs: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,6 +300,10 @@ pub struct WorldPlan {
     pub rooms: Vec<RoomPlan>,
     pub spawn_x: isize,
     pub spawn_y: isize,
+    #[serde(default)]
+    pub spawn_z: isize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respawn_delay: Option<u64>,
 }
 
 impl WorldPlan {
@@ -38,6 +312,8 @@ impl WorldPlan {
             rooms: Vec::new(),
             spawn_x: 0,
             spawn_y: 0,
+            spawn_z: 0,
+            respawn_delay: None,
         }
     }
 
@@ -97,23 +373,111 @@ impl WorldPlan {
         (-min_y) as usize
     }
 
-    pub fn from_seeder(mut seeder: seed::Seeder) -> WorldPlan {
+    pub fn resolve_monsters(&mut self, seeder: &mut seed::Seeder) {
+        for room in self.rooms.iter_mut() {
+            if let Some(monsters_plan) = &room.monsters {
+                room.monsters = Some(MonstersPlan::Defined(monsters_plan.resolve(seeder)));
+            }
+        }
+    }
+
+    pub fn positions(&self) -> HashMap<Position, usize> {
+        self.rooms
+            .iter()
+            .enumerate()
+            .map(|(index, room)| ((room.x, room.y, room.z), index))
+            .collect()
+    }
+
+    pub fn connects(
+        &self,
+        positions: &HashMap<Position, usize>,
+        room: &RoomPlan,
+        direction: &data_model::Direction,
+    ) -> bool {
+        let neighbour = step((room.x, room.y, room.z), direction);
+
+        match positions.get(&neighbour) {
+            Some(index) => {
+                let next_room = &self.rooms[*index];
+
+                allows(&room.exits, &room.stairs, direction)
+                    && allows(&next_room.exits, &next_room.stairs, &direction.opposite())
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_levels(&self) -> Vec<isize> {
+        let mut levels: Vec<isize> = self.rooms.iter().map(|room| room.z).collect();
+        levels.sort_unstable();
+        levels.dedup();
+
+        levels
+    }
+
+    pub fn from_seeder_with_style(
+        seeder: &mut seed::Seeder,
+        style: Style,
+        floors: usize,
+    ) -> WorldPlan {
+        let mut world_plan = WorldPlan::new();
+        let mut origin = (0, 0);
+
+        for level in 0..std::cmp::max(floors, 1) as isize {
+            let floor = Floor { level, origin };
+            let mut floor_plan = match style {
+                Style::Corridors => WorldPlan::from_seeder(seeder, floor),
+                Style::Bsp => gen_bsp::from_seeder(seeder, floor),
+                Style::Caves => gen_cave::from_seeder(seeder, floor),
+            };
+
+            if level > 0 {
+                floor_plan.rooms[0].description = Some(i18n::Message::FloorLanding(level).into());
+                floor_plan.rooms[0].stairs = Some(Stairs::Down);
+
+                if let Some(landing) = world_plan
+                    .rooms
+                    .iter_mut()
+                    .find(|room| room.x == origin.0 && room.y == origin.1 && room.z == level - 1)
+                {
+                    landing.stairs = Some(
+                        landing
+                            .stairs
+                            .map_or(Stairs::Up, |stairs| stairs.with(Stairs::Up)),
+                    );
+                }
+            }
+
+            if let Some(farthest) = floor_plan
+                .rooms
+                .iter()
+                .max_by_key(|room| (room.x - origin.0).pow(2) + (room.y - origin.1).pow(2))
+            {
+                origin = (farthest.x, farthest.y);
+            }
+
+            world_plan.rooms.append(&mut floor_plan.rooms);
+        }
+
+        world_plan.describe_spawn();
+
+        world_plan
+    }
+
+    pub fn from_seeder(seeder: &mut seed::Seeder, floor: Floor) -> WorldPlan {
         println!("Generating map");
 
         let mut world_plan = WorldPlan::new();
 
         let corridor_count = seeder.seed_u32_bounded(150, 600);
         let mut coords_list: Vec<(isize, isize)> = vec![(0, 0)];
+        // Corridors cross each other, only the first room at a position is kept.
+        let mut placed: HashSet<(isize, isize)> = coords_list.iter().cloned().collect();
 
         let mut direction = 0;
 
-        world_plan.rooms.push(RoomPlan {
-            x: 0,
-            y: 0,
-            description: None,
-            monsters: None,
-            hp_regen: None,
-        });
+        world_plan.rooms.push(RoomPlan::spawn(floor));
 
         println!("Generated spawn");
 
@@ -126,48 +490,20 @@ impl WorldPlan {
 
             for _ in 0..corridor_length {
                 coords = vector_function(coords);
-                coords_list.push(coords.clone());
-                if coords.0 != 0 || coords.1 != 0 {
-                    let difficulty_multiplier = WorldPlan::get_difficulty_multiplier(
-                        corridor_count.clone() * 30,
-                        coords.clone(),
-                    );
-                    let hp_regen =
-                        Some(seeder.seed_u32_bounded(0, 1) as u32 * difficulty_multiplier);
-
-                    world_plan.rooms.push(RoomPlan {
-                        x: coords.0,
-                        y: coords.1,
-                        description: Some(format!(
-                            "You are at coordinates ({},{}). This room has a difficulty of {}{}",
-                            coords.0,
-                            coords.1,
-                            difficulty_multiplier,
-                            match hp_regen {
-                                None => String::from(""),
-                                Some(0) => String::from(""),
-                                Some(value) => format!(" and regenerates {} HP", value),
-                            }
-                        )),
-                        monsters: Some(MonstersPlan::Random(
-                            seeder.seed_u32_bounded(1, 3) as usize * difficulty_multiplier as usize,
-                        )),
-                        hp_regen: hp_regen,
-                    });
+                coords_list.push(coords);
+                if placed.insert(coords) {
+                    world_plan.rooms.push(RoomPlan::from_seeder(
+                        seeder,
+                        coords,
+                        corridor_count * 30,
+                        floor,
+                    ));
                 }
             }
 
             println!("Generated corridor {}/{}", i + 1, corridor_count);
         }
 
-        let room_count = world_plan.rooms.len();
-
-        world_plan.rooms[0].description = Some(format!(
-            "Welcome! This is the spawn room. There are {} other {} to explore",
-            room_count - 1,
-            if room_count - 1 > 1 { "rooms" } else { "room" },
-        ));
-
         println!("Finished generating map");
 
         world_plan
@@ -175,14 +511,20 @@ impl WorldPlan {
 
     fn get_vector_function(direction: u32) -> fn((isize, isize)) -> (isize, isize) {
         match direction % 4 {
-            0 => (|coords: (isize, isize)| (coords.0, coords.1 + 1)),
-            1 => (|coords: (isize, isize)| (coords.0, coords.1 - 1)),
-            2 => (|coords: (isize, isize)| (coords.0 - 1, coords.1)),
-            _ => (|coords: (isize, isize)| (coords.0 + 1, coords.1)),
+            0 => |coords: (isize, isize)| (coords.0, coords.1 + 1),
+            1 => |coords: (isize, isize)| (coords.0, coords.1 - 1),
+            2 => |coords: (isize, isize)| (coords.0 - 1, coords.1),
+            _ => |coords: (isize, isize)| (coords.0 + 1, coords.1),
         }
     }
 
-    fn get_difficulty_multiplier(room_count: u32, coords: (isize, isize)) -> u32 {
+    pub fn describe_spawn(&mut self) {
+        let room_count = self.rooms.len();
+
+        self.rooms[0].description = Some(i18n::Message::SpawnRoom(room_count - 1).into());
+    }
+
+    pub fn get_difficulty_multiplier(room_count: u32, coords: (isize, isize)) -> u32 {
         let distance_from_spawn = ((coords.0.pow(2) + coords.1.pow(2)) as f32).sqrt();
         let world_radius = (room_count as f32 / std::f32::consts::PI).sqrt();
 
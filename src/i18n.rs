@@ -0,0 +1,677 @@
+use crate::data_model;
+
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "fr")]
+    Fr,
+}
+
+impl Lang {
+    // Picks the supported language with the highest quality value, e.g. from
+    // `fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5`.
+    pub fn from_accept_language(header: &str) -> Option<Lang> {
+        let mut best: Option<(Lang, f32)> = None;
+
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let lang = match Lang::from_str(parts.next().unwrap_or("").trim()) {
+                Ok(lang) => lang,
+                Err(_) => continue,
+            };
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .filter_map(|quality| f32::from_str(quality).ok())
+                .next()
+                .unwrap_or(1.0);
+
+            match best {
+                Some((_, best_quality)) if best_quality >= quality => (),
+                _ if quality > 0.0 => best = Some((lang, quality)),
+                _ => (),
+            }
+        }
+
+        best.map(|(lang, _)| lang)
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Lang, String> {
+        let primary = s.split(|c| c == '-' || c == '_').next().unwrap_or("");
+
+        match primary.to_ascii_lowercase().as_str() {
+            "en" => Ok(Lang::En),
+            "fr" => Ok(Lang::Fr),
+            _ => Err(format!("Unsupported language: {}", s)),
+        }
+    }
+}
+
+impl<'v> rocket::request::FromFormValue<'v> for Lang {
+    type Error = String;
+
+    fn from_form_value(form_value: &'v rocket::http::RawStr) -> Result<Lang, String> {
+        Lang::from_str(form_value.as_str())
+    }
+}
+
+// Either a text written by hand, in a single language or translated in every
+// supported language, or a message from the catalog translated when it is
+// read, so that plans and snapshots follow changes to the catalog.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Text {
+    Plain(String),
+    Translated { en: String, fr: String },
+    Message(Message),
+}
+
+impl Text {
+    pub fn get(&self, lang: Lang) -> String {
+        match self {
+            Text::Plain(text) => text.clone(),
+            Text::Translated { en, fr } => match lang {
+                Lang::En => en.clone(),
+                Lang::Fr => fr.clone(),
+            },
+            Text::Message(message) => message.translate(lang),
+        }
+    }
+}
+
+impl From<Message> for Text {
+    fn from(message: Message) -> Text {
+        Text::Message(message)
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Text {
+        Text::Plain(text)
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Text {
+        Text::Plain(String::from(text))
+    }
+}
+
+// Serialized as `{"key": "spawn_room", "args": 12}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "key", content = "args", rename_all = "snake_case")]
+pub enum Message {
+    Disappeared,
+    Wall,
+    LockedByKey(String),
+    LockedUntilCleared,
+    LockedUntilLevel(u32),
+    DiffRoom,
+    Unauthorized,
+    BadCredentials,
+    EntityNotFound,
+    ItemNotFound,
+    RateLimited,
+    TooManyStreams,
+    Banned(Option<String>),
+    BadRequest,
+    Unprocessable,
+    NotFound,
+    Internal,
+    CannotEquipPotion,
+    CannotEquipKey,
+    CannotUseItem,
+    InvalidUsername(usize),
+    UsernameTaken,
+    AlreadyInRoom,
+    InvalidAddress(String),
+    InvalidPrefix(String),
+    InvalidExpiry(String),
+    InvalidBanReason,
+    AlreadyBanned(String),
+    NotBanned(String),
+    LanguageChanged,
+    LineTooLong(usize),
+    Room {
+        x: isize,
+        y: isize,
+        level: isize,
+        difficulty: u32,
+        hp_regen: u32,
+    },
+    FloorLanding(isize),
+    SpawnRoom(usize),
+    DefaultRoom,
+    SimpleMonster,
+    AnotherPlayer,
+    Weapon,
+    Armour,
+    Potion,
+    Key,
+    TelnetHelp,
+    UnknownCommand,
+    UnknownTarget,
+    UnknownItem,
+    Welcome(String),
+    DirectionName(data_model::Direction),
+    Exits(String),
+    NoExits,
+    Occupants(String),
+    Yourself(String),
+    OnTheFloor(String),
+    WeaponBonus(u32),
+    ArmourBonus(u32),
+    PotionHeal(u32),
+    OpensDoors,
+    InventoryStats {
+        life: u32,
+        total_life: u32,
+        dp: u32,
+    },
+    Equipped,
+    EntityStats {
+        description: String,
+        level: u32,
+        life: u32,
+        total_life: u32,
+    },
+    FightReport {
+        attacker: String,
+        defender: String,
+        damage: u32,
+        taken: u32,
+        attacker_hp: u32,
+        defender_hp: u32,
+    },
+    Appeared(String),
+    Vanished(String),
+    Entered(String),
+    Left {
+        guid: String,
+        direction: data_model::Direction,
+    },
+    Died(String),
+    Spawned(String),
+    ReachedLevel {
+        guid: String,
+        level: u32,
+    },
+    Announcement(String),
+}
+
+impl Message {
+    pub fn translate(&self, lang: Lang) -> String {
+        match lang {
+            Lang::En => self.english(),
+            Lang::Fr => self.french(),
+        }
+    }
+
+    fn english(&self) -> String {
+        match self {
+            Message::Disappeared => String::from(
+                "You were either killed or disconnected. In any case you disappeared ¯\\_(ツ)_/¯",
+            ),
+            Message::Wall => String::from(
+                "Ouch! You bumped into a wall! Consider yourself lucky I didn't make you lose HP when it happens... Now look where you're going!",
+            ),
+            Message::LockedByKey(key) => {
+                format!("This door is locked. You need the {} key to open it", key)
+            }
+            Message::LockedUntilCleared => String::from(
+                "This door is sealed until every monster guarding it is dead",
+            ),
+            Message::LockedUntilLevel(level) => format!(
+                "This door only opens for adventurers of level {} or more",
+                level
+            ),
+            Message::DiffRoom => String::from(
+                "Room mismatch! What happened? Did you try to hack the game?",
+            ),
+            Message::Unauthorized => String::from(
+                "Missing or invalid session token. Did you try to take control of someone else?",
+            ),
+            Message::BadCredentials => String::from("Unknown username or wrong password"),
+            Message::EntityNotFound => {
+                String::from("Entity was not found in world. Maybe it died or was disconnected")
+            }
+            Message::ItemNotFound => {
+                String::from("This item is not here. Maybe someone else picked it up")
+            }
+            Message::RateLimited => {
+                String::from("Slow down! You are acting faster than your legs can carry you")
+            }
+            Message::TooManyStreams => String::from(
+                "Too many players are listening to events right now, please try again later",
+            ),
+            Message::Banned(reason) => match reason {
+                Some(reason) => format!(
+                    "You have been struck by the holy Banhammer, now get out! ({})",
+                    reason
+                ),
+                None => String::from("You have been struck by the holy Banhammer, now get out!"),
+            },
+            Message::BadRequest => String::from("This request is malformed"),
+            Message::Unprocessable => {
+                String::from("The request body does not have the expected format")
+            }
+            Message::NotFound => String::from("There is nothing at this address"),
+            Message::Internal => {
+                String::from("Something went wrong on the server, please try again later")
+            }
+            Message::CannotEquipPotion => {
+                String::from("You cannot equip a potion, try drinking it instead")
+            }
+            Message::CannotEquipKey => String::from(
+                "Keys open doors from your inventory, there is no need to equip them",
+            ),
+            Message::CannotUseItem => {
+                String::from("This item cannot be used, try equipping it instead")
+            }
+            Message::InvalidUsername(length) => format!(
+                "Usernames must be 1 to {} letters, digits, dashes or underscores",
+                length
+            ),
+            Message::UsernameTaken => String::from("This username is already taken"),
+            Message::AlreadyInRoom => String::from("Entity is already present in this room!"),
+            Message::InvalidAddress(address) => format!("{} is not a valid IP address", address),
+            Message::InvalidPrefix(prefix) => format!("{} is not a valid prefix length", prefix),
+            Message::InvalidExpiry(timestamp) => {
+                format!("{} is not a valid expiry timestamp", timestamp)
+            }
+            Message::InvalidBanReason => {
+                String::from("Ban reasons cannot contain control characters")
+            }
+            Message::AlreadyBanned(network) => format!("{} is already banned", network),
+            Message::NotBanned(network) => format!("{} is not banned", network),
+            Message::LanguageChanged => String::from("The game now speaks English"),
+            Message::LineTooLong(length) => {
+                format!("Commands are limited to {} characters, goodbye", length)
+            }
+            Message::Room {
+                x,
+                y,
+                level,
+                difficulty,
+                hp_regen,
+            } => format!(
+                "You are at coordinates ({},{}){}. This room has a difficulty of {}{}",
+                x,
+                y,
+                if *level > 0 {
+                    format!(" on floor {}", level)
+                } else {
+                    String::from("")
+                },
+                difficulty,
+                if *hp_regen > 0 {
+                    format!(" and regenerates {} HP", hp_regen)
+                } else {
+                    String::from("")
+                }
+            ),
+            Message::FloorLanding(level) => format!(
+                "You reached floor {}. Stairs lead back down, and the monsters here are tougher",
+                level
+            ),
+            Message::SpawnRoom(others) => format!(
+                "Welcome! This is the spawn room. There are {} other {} to explore",
+                others,
+                if *others > 1 { "rooms" } else { "room" },
+            ),
+            Message::DefaultRoom => String::from("A room"),
+            Message::SimpleMonster => String::from("A simple monster"),
+            Message::AnotherPlayer => String::from("Another player"),
+            Message::Weapon => String::from("A weapon"),
+            Message::Armour => String::from("A piece of armour"),
+            Message::Potion => String::from("A healing potion"),
+            Message::Key => String::from("A key"),
+            Message::TelnetHelp => String::from(
+                "Commands: look, go <n|e|s|w|u|d>, examine <id>, hit <id>, inventory, take <id>, drop <id>, equip <id>, use <id>, lang <en|fr>, help, quit",
+            ),
+            Message::UnknownCommand => {
+                String::from("Unknown command, type help for the list of commands")
+            }
+            Message::UnknownTarget => {
+                String::from("There is nobody matching that id in this room")
+            }
+            Message::UnknownItem => String::from("There is no item matching that id"),
+            Message::Welcome(guid) => format!("Welcome to the Mungeon! You are {}", guid),
+            Message::DirectionName(direction) => String::from(match direction {
+                data_model::Direction::N => "north",
+                data_model::Direction::E => "east",
+                data_model::Direction::S => "south",
+                data_model::Direction::W => "west",
+                data_model::Direction::U => "up",
+                data_model::Direction::D => "down",
+            }),
+            Message::Exits(exits) => format!("Exits: {}", exits),
+            Message::NoExits => String::from("none"),
+            Message::Occupants(guids) => format!("Here: {}", guids),
+            Message::Yourself(guid) => format!("{} (you)", guid),
+            Message::OnTheFloor(item) => format!("On the floor: {}", item),
+            Message::WeaponBonus(dp) => format!("+{} damage", dp),
+            Message::ArmourBonus(hp) => format!("+{} max HP", hp),
+            Message::PotionHeal(hp) => format!("heals {} HP", hp),
+            Message::OpensDoors => String::from("opens doors"),
+            Message::InventoryStats {
+                life,
+                total_life,
+                dp,
+            } => format!(
+                "You have {}/{} HP and deal {} damage",
+                life, total_life, dp
+            ),
+            Message::Equipped => String::from("[equipped]"),
+            Message::EntityStats {
+                description,
+                level,
+                life,
+                total_life,
+            } => format!(
+                "{} (level {}, {}/{} HP)",
+                description, level, life, total_life
+            ),
+            Message::FightReport {
+                attacker,
+                defender,
+                damage,
+                taken,
+                attacker_hp,
+                defender_hp,
+            } => format!(
+                "{} hits {} for {} damage and takes {} back. {} has {} HP left, {} has {} HP left",
+                attacker, defender, damage, taken, attacker, attacker_hp, defender, defender_hp
+            ),
+            Message::Appeared(guid) => format!("{} appeared out of thin air", guid),
+            Message::Vanished(guid) => format!("{} vanished", guid),
+            Message::Entered(guid) => format!("{} entered the room", guid),
+            Message::Left { guid, direction } => format!(
+                "{} left the room heading {}",
+                guid,
+                Message::DirectionName(direction.clone()).english()
+            ),
+            Message::Died(guid) => format!("{} died", guid),
+            Message::Spawned(guid) => format!("{} crawled out of the shadows", guid),
+            Message::ReachedLevel { guid, level } => format!("{} reached level {}", guid, level),
+            Message::Announcement(message) => format!("[Announcement] {}", message),
+        }
+    }
+
+    fn french(&self) -> String {
+        match self {
+            Message::Disappeared => String::from(
+                "Vous avez été tué ou déconnecté. Dans tous les cas, vous avez disparu ¯\\_(ツ)_/¯",
+            ),
+            Message::Wall => String::from(
+                "Aïe ! Vous vous êtes cogné contre un mur ! Estimez-vous heureux que je ne vous fasse pas perdre de PV quand ça arrive... Maintenant, regardez où vous allez !",
+            ),
+            Message::LockedByKey(key) => format!(
+                "Cette porte est verrouillée. Il vous faut la clé {} pour l'ouvrir",
+                key
+            ),
+            Message::LockedUntilCleared => String::from(
+                "Cette porte reste scellée tant que les monstres qui la gardent sont en vie",
+            ),
+            Message::LockedUntilLevel(level) => format!(
+                "Cette porte ne s'ouvre qu'aux aventuriers de niveau {} ou plus",
+                level
+            ),
+            Message::DiffRoom => String::from(
+                "Vous n'êtes pas dans la même salle ! Que s'est-il passé ? Auriez-vous essayé de tricher ?",
+            ),
+            Message::Unauthorized => String::from(
+                "Jeton de session manquant ou invalide. Auriez-vous essayé de contrôler quelqu'un d'autre ?",
+            ),
+            Message::BadCredentials => {
+                String::from("Nom d'utilisateur inconnu ou mot de passe incorrect")
+            }
+            Message::EntityNotFound => String::from(
+                "Cette entité est introuvable. Elle est peut-être morte ou s'est déconnectée",
+            ),
+            Message::ItemNotFound => {
+                String::from("Cet objet n'est pas ici. Quelqu'un l'a peut-être ramassé")
+            }
+            Message::RateLimited => String::from(
+                "Doucement ! Vous agissez plus vite que vos jambes ne peuvent vous porter",
+            ),
+            Message::TooManyStreams => String::from(
+                "Trop de joueurs écoutent les événements en ce moment, veuillez réessayer plus tard",
+            ),
+            Message::Banned(reason) => match reason {
+                Some(reason) => format!(
+                    "Le Banhammer sacré s'est abattu sur vous, maintenant dehors ! ({})",
+                    reason
+                ),
+                None => String::from(
+                    "Le Banhammer sacré s'est abattu sur vous, maintenant dehors !",
+                ),
+            },
+            Message::BadRequest => String::from("Cette requête est mal formée"),
+            Message::Unprocessable => {
+                String::from("Le corps de la requête n'a pas le format attendu")
+            }
+            Message::NotFound => String::from("Il n'y a rien à cette adresse"),
+            Message::Internal => String::from(
+                "Quelque chose s'est mal passé sur le serveur, veuillez réessayer plus tard",
+            ),
+            Message::CannotEquipPotion => {
+                String::from("Une potion ne s'équipe pas, essayez plutôt de la boire")
+            }
+            Message::CannotEquipKey => String::from(
+                "Les clés ouvrent les portes depuis votre inventaire, inutile de les équiper",
+            ),
+            Message::CannotUseItem => {
+                String::from("Cet objet ne s'utilise pas, essayez plutôt de l'équiper")
+            }
+            Message::InvalidUsername(length) => format!(
+                "Les noms d'utilisateur font de 1 à {} caractères parmi les lettres, chiffres, tirets et tirets bas",
+                length
+            ),
+            Message::UsernameTaken => String::from("Ce nom d'utilisateur est déjà pris"),
+            Message::AlreadyInRoom => String::from("Cette entité est déjà dans la salle !"),
+            Message::InvalidAddress(address) => {
+                format!("{} n'est pas une adresse IP valide", address)
+            }
+            Message::InvalidPrefix(prefix) => {
+                format!("{} n'est pas une longueur de préfixe valide", prefix)
+            }
+            Message::InvalidExpiry(timestamp) => {
+                format!("{} n'est pas une date d'expiration valide", timestamp)
+            }
+            Message::InvalidBanReason => String::from(
+                "Le motif d'un bannissement ne peut pas contenir de caractères de contrôle",
+            ),
+            Message::AlreadyBanned(network) => format!("{} est déjà banni", network),
+            Message::NotBanned(network) => format!("{} n'est pas banni", network),
+            Message::LanguageChanged => String::from("Le jeu parle désormais français"),
+            Message::LineTooLong(length) => format!(
+                "Les commandes sont limitées à {} caractères, au revoir",
+                length
+            ),
+            Message::Room {
+                x,
+                y,
+                level,
+                difficulty,
+                hp_regen,
+            } => format!(
+                "Vous êtes aux coordonnées ({},{}){}. Cette salle a une difficulté de {}{}",
+                x,
+                y,
+                if *level > 0 {
+                    format!(" à l'étage {}", level)
+                } else {
+                    String::from("")
+                },
+                difficulty,
+                if *hp_regen > 0 {
+                    format!(" et régénère {} PV", hp_regen)
+                } else {
+                    String::from("")
+                }
+            ),
+            Message::FloorLanding(level) => format!(
+                "Vous avez atteint l'étage {}. Un escalier redescend, et les monstres ici sont plus coriaces",
+                level
+            ),
+            Message::SpawnRoom(others) => format!(
+                "Bienvenue ! Ceci est la salle d'apparition. Il y a {} {} à explorer",
+                others,
+                if *others > 1 {
+                    "autres salles"
+                } else {
+                    "autre salle"
+                },
+            ),
+            Message::DefaultRoom => String::from("Une salle"),
+            Message::SimpleMonster => String::from("Un simple monstre"),
+            Message::AnotherPlayer => String::from("Un autre joueur"),
+            Message::Weapon => String::from("Une arme"),
+            Message::Armour => String::from("Une pièce d'armure"),
+            Message::Potion => String::from("Une potion de soin"),
+            Message::Key => String::from("Une clé"),
+            Message::TelnetHelp => String::from(
+                "Commandes : look, go <n|e|s|w|u|d>, examine <id>, hit <id>, inventory, take <id>, drop <id>, equip <id>, use <id>, lang <en|fr>, help, quit",
+            ),
+            Message::UnknownCommand => {
+                String::from("Commande inconnue, tapez help pour la liste des commandes")
+            }
+            Message::UnknownTarget => {
+                String::from("Personne ne correspond à cet identifiant dans cette salle")
+            }
+            Message::UnknownItem => String::from("Aucun objet ne correspond à cet identifiant"),
+            Message::Welcome(guid) => format!("Bienvenue dans le Mungeon ! Vous êtes {}", guid),
+            Message::DirectionName(direction) => String::from(match direction {
+                data_model::Direction::N => "nord",
+                data_model::Direction::E => "est",
+                data_model::Direction::S => "sud",
+                data_model::Direction::W => "ouest",
+                data_model::Direction::U => "haut",
+                data_model::Direction::D => "bas",
+            }),
+            Message::Exits(exits) => format!("Sorties : {}", exits),
+            Message::NoExits => String::from("aucune"),
+            Message::Occupants(guids) => format!("Ici : {}", guids),
+            Message::Yourself(guid) => format!("{} (vous)", guid),
+            Message::OnTheFloor(item) => format!("Au sol : {}", item),
+            Message::WeaponBonus(dp) => format!("+{} dégâts", dp),
+            Message::ArmourBonus(hp) => format!("+{} PV max", hp),
+            Message::PotionHeal(hp) => format!("soigne {} PV", hp),
+            Message::OpensDoors => String::from("ouvre des portes"),
+            Message::InventoryStats {
+                life,
+                total_life,
+                dp,
+            } => format!(
+                "Vous avez {}/{} PV et infligez {} dégâts",
+                life, total_life, dp
+            ),
+            Message::Equipped => String::from("[équipé]"),
+            Message::EntityStats {
+                description,
+                level,
+                life,
+                total_life,
+            } => format!(
+                "{} (niveau {}, {}/{} PV)",
+                description, level, life, total_life
+            ),
+            Message::FightReport {
+                attacker,
+                defender,
+                damage,
+                taken,
+                attacker_hp,
+                defender_hp,
+            } => format!(
+                "{} inflige {} dégâts à {} et en reçoit {} en retour. Il reste {} PV à {} et {} PV à {}",
+                attacker, damage, defender, taken, attacker_hp, attacker, defender_hp, defender
+            ),
+            Message::Appeared(guid) => format!("{} est apparu de nulle part", guid),
+            Message::Vanished(guid) => format!("{} a disparu", guid),
+            Message::Entered(guid) => format!("{} est entré dans la salle", guid),
+            Message::Left { guid, direction } => format!(
+                "{} a quitté la salle direction {}",
+                guid,
+                Message::DirectionName(direction.clone()).french()
+            ),
+            Message::Died(guid) => format!("{} est mort", guid),
+            Message::Spawned(guid) => format!("{} a surgi des ténèbres", guid),
+            Message::ReachedLevel { guid, level } => {
+                format!("{} a atteint le niveau {}", guid, level)
+            }
+            Message::Announcement(message) => format!("[Annonce] {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_preferred_language() {
+        assert_eq!(Lang::from_accept_language("fr"), Some(Lang::Fr));
+        assert_eq!(
+            Lang::from_accept_language("en-GB, fr;q=0.8"),
+            Some(Lang::En)
+        );
+        assert_eq!(
+            Lang::from_accept_language("en;q=0.5, fr;q=0.9"),
+            Some(Lang::Fr)
+        );
+        assert_eq!(
+            Lang::from_accept_language("de, fr-CH;q=0.7, en;q=0.3"),
+            Some(Lang::Fr)
+        );
+    }
+
+    #[test]
+    fn handles_region_subtags() {
+        assert_eq!(Lang::from_accept_language("fr-CA"), Some(Lang::Fr));
+        assert_eq!(Lang::from_accept_language("FR_be"), Some(Lang::Fr));
+        assert_eq!(Lang::from_accept_language("en-US"), Some(Lang::En));
+    }
+
+    #[test]
+    fn ignores_refused_and_unsupported_languages() {
+        assert_eq!(
+            Lang::from_accept_language("fr;q=0, en;q=0.1"),
+            Some(Lang::En)
+        );
+        assert_eq!(Lang::from_accept_language("fr;q=0"), None);
+        assert_eq!(Lang::from_accept_language("de, *;q=0.5"), None);
+        assert_eq!(Lang::from_accept_language(""), None);
+    }
+
+    #[test]
+    fn stores_catalog_messages_as_keys() {
+        let text = Text::from(Message::SpawnRoom(3));
+        let json = serde_json::to_string(&text).unwrap();
+
+        assert_eq!(json, r#"{"key":"spawn_room","args":3}"#);
+        assert_eq!(serde_json::from_str::<Text>(&json).unwrap(), text);
+        assert_eq!(
+            serde_json::from_str::<Text>(r#"{"en":"A cave","fr":"Une grotte"}"#)
+                .unwrap()
+                .get(Lang::Fr),
+            "Une grotte"
+        );
+        assert_eq!(
+            serde_json::from_str::<Text>(r#""A cave""#)
+                .unwrap()
+                .get(Lang::Fr),
+            "A cave"
+        );
+    }
+}
@@ -3,91 +3,256 @@
 #[macro_use]
 extern crate rocket;
 
+mod accounts;
+mod bans;
+mod config;
 mod data_model;
 mod dump;
+mod events;
+mod gen_bsp;
+mod gen_cave;
 mod gen_plan;
+mod i18n;
+mod ratelimit;
 mod seed;
 mod server;
+mod snapshot;
+mod telnet;
+mod validate;
 mod world;
 
-use std::env;
+use clap::{Args, Parser, Subcommand};
+
 use std::error;
 use std::fs;
-use std::io::{self, BufRead};
-use std::str::FromStr;
+use std::time;
 
-static ERROR_ARGUMENT_PARSE: &str = "Could not parse argument";
 static NO_GEN_ERROR: &str = "No input file or seed provided";
 static FILE_READ_ERROR: &str = "Could not read file";
 static GEN_PARSE_ERROR: &str = "Error while parsing generation plan";
+static INVALID_PLAN_ERROR: &str = "Generation plan has errors, refusing to continue";
 
-static BAN_LIST_PATH: &str = "ban_list.txt";
+#[derive(Parser)]
+#[command(version, about = "Multiplayer dungeon server")]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "TOML configuration file [default: mungeon.toml]"
+    )]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
 
-fn main() -> Result<(), Box<dyn error::Error>> {
-    let args: Vec<String> = env::args().collect();
-
-    let mut filename = String::new();
-    let mut data = String::new();
-    let mut seed = String::new();
-    let mut dump = false;
-
-    for arg in args.iter().skip(1) {
-        if arg.starts_with("gen=") {
-            filename = arg[4..arg.len()].to_string();
-            data =
-                fs::read_to_string(&filename).expect(&format!("{} {}", FILE_READ_ERROR, &filename));
-        } else if arg.starts_with("seed=") {
-            let value = arg[5..arg.len()].to_string();
-            if value == "random" {
-                for _ in 0..50 {
-                    seed.push(fastrand::digit(16));
-                }
-            } else {
-                seed = value;
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Generate or resume a world and serve it")]
+    Serve {
+        #[command(flatten)]
+        plan: PlanArgs,
+        #[arg(long, conflicts_with_all = ["gen", "seed"], help = "Resume a saved world")]
+        resume: Option<String>,
+    },
+    #[command(about = "Render the generation plan as a map")]
+    Dump {
+        #[command(flatten)]
+        plan: PlanArgs,
+        #[arg(long, default_value = "png", help = "png, svg, ascii or unicode")]
+        format: dump::Format,
+        #[arg(
+            long,
+            help = "Output file [default: world_dump.png or world_dump.svg, text maps go to stdout]"
+        )]
+        output: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u32).range(1..=dump::MAX_TILE_SIZE as i64),
+            help = "Size of a room in pixels"
+        )]
+        tile_size: u32,
+        #[arg(long, help = "Draw a colour legend under the map")]
+        legend: bool,
+    },
+    #[command(about = "Check a generation plan for errors")]
+    Validate {
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+    #[command(about = "Export the generation plan as an editable JSON file")]
+    GenPlan {
+        #[command(flatten)]
+        plan: PlanArgs,
+        #[arg(long, default_value = "world_plan.json")]
+        output: String,
+        #[arg(long, help = "Indent the JSON output")]
+        pretty: bool,
+        #[arg(long, help = "Write out the stats of randomly generated monsters")]
+        resolve_monsters: bool,
+    },
+}
+
+#[derive(Args)]
+struct PlanArgs {
+    #[arg(long, help = "JSON generation plan")]
+    gen: Option<String>,
+    #[arg(long, help = "Generation seed, or \"random\"")]
+    seed: Option<String>,
+    #[arg(long, default_value = "corridors", help = "corridors, bsp or caves")]
+    style: gen_plan::Style,
+    #[arg(long, default_value_t = 1)]
+    floors: usize,
+}
+
+struct LoadedPlan {
+    plan: gen_plan::WorldPlan,
+    seeder: seed::Seeder,
+    source: Option<String>,
+}
+
+fn load_plan(args: PlanArgs) -> Result<LoadedPlan, Box<dyn error::Error>> {
+    let seed = match args.seed {
+        Some(value) if value == "random" => {
+            let mut seed = String::new();
+            for _ in 0..50 {
+                seed.push(fastrand::digit(16));
             }
-        } else if arg == "--dump" {
-            dump = true;
-        } else {
-            panic!("{} {}", ERROR_ARGUMENT_PARSE, arg);
+            println!("Using seed {}", seed);
+            Some(seed)
         }
-    }
+        value => value,
+    };
+
+    match (args.gen, seed) {
+        (Some(filename), seed) => {
+            let data = fs::read_to_string(&filename)
+                .map_err(|error| format!("{} {}: {}", FILE_READ_ERROR, filename, error))?;
+            let plan = serde_json::from_str(data.as_str())
+                .map_err(|error| format!("{}: {}", GEN_PARSE_ERROR, error))?;
 
-    let world: world::World;
-    let plan: gen_plan::WorldPlan;
+            Ok(LoadedPlan {
+                plan,
+                seeder: match seed {
+                    Some(seed) => seed::Seeder::from_seed(seed),
+                    None => seed::Seeder::random(),
+                },
+                source: Some(data),
+            })
+        }
+        (None, Some(seed)) => {
+            let mut seeder = seed::Seeder::from_seed(seed);
+            let plan =
+                gen_plan::WorldPlan::from_seeder_with_style(&mut seeder, args.style, args.floors);
 
-    if filename == "" {
-        if seed == "" {
-            panic!("{}", NO_GEN_ERROR);
-        } else {
-            let seeder: seed::Seeder = seed::Seeder::try_from_seed(seed)?;
-            plan = gen_plan::WorldPlan::from_seeder(seeder);
-            world = world::World::generate(&plan);
+            Ok(LoadedPlan {
+                plan,
+                seeder,
+                source: None,
+            })
         }
-    } else {
-        plan = serde_json::from_str(data.as_str()).expect(GEN_PARSE_ERROR);
-        world = world::World::generate(&plan);
+        (None, None) => Err(Box::from(NO_GEN_ERROR)),
+    }
+}
+
+fn check_plan(loaded: &LoadedPlan) -> Result<usize, Box<dyn error::Error>> {
+    let issues = validate::validate(&loaded.plan, loaded.source.as_deref());
+
+    for issue in issues.iter() {
+        println!("{}", issue);
     }
 
-    if dump {
-        dump::dump_world(&plan);
-    } else {
-        let mut banned_ips = Vec::new();
-
-        match std::fs::File::open(std::path::Path::new(BAN_LIST_PATH)) {
-            Ok(file) => {
-                for ip_str in io::BufReader::new(file).lines() {
-                    match std::net::IpAddr::from_str(ip_str?.as_str()) {
-                        Ok(ip_addr) => {
-                            banned_ips.push(ip_addr);
-                        }
-                        Err(_) => (),
-                    }
-                }
+    if validate::has_errors(&issues) {
+        return Err(Box::from(INVALID_PLAN_ERROR));
+    }
+
+    Ok(issues.len())
+}
+
+fn configure_world(world: &mut world::World, config: &config::Config) {
+    world.afk_threshold = time::Duration::from_secs(config.server.afk_timeout);
+    world.player_hp = config.player.hp;
+    world.player_dp = config.player.dp;
+}
+
+fn serve(
+    config: config::Config,
+    plan: PlanArgs,
+    resume: Option<String>,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut world = match resume {
+        Some(resume_filename) => {
+            let world = snapshot::load(resume_filename.as_str())?;
+            println!("Resumed world from {}", resume_filename);
+            world
+        }
+        None => {
+            let loaded = load_plan(plan)?;
+
+            if loaded.source.is_some() {
+                check_plan(&loaded)?;
             }
-            Err(_) => (),
+
+            world::World::generate(&loaded.plan, loaded.seeder)
         }
+    };
+
+    configure_world(&mut world, &config);
+    world.accounts = accounts::Accounts::load(config.paths.accounts.clone())?;
 
-        server::launch(world, banned_ips)?;
+    let banned_ips = bans::BanList::load(config.server.ban_list.clone())?;
+    server::launch(world, banned_ips, config)
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let cli = Cli::parse();
+    let config = config::Config::load(cli.config)?;
+
+    match cli.command {
+        Command::Serve { plan, resume } => serve(config, plan, resume)?,
+        Command::Dump {
+            plan,
+            format,
+            output,
+            tile_size,
+            legend,
+        } => dump::dump_world(
+            &load_plan(plan)?.plan,
+            &dump::DumpOptions {
+                format,
+                tile_size,
+                output,
+                legend,
+            },
+        )?,
+        Command::Validate { plan } => {
+            let warnings = check_plan(&load_plan(plan)?)?;
+            println!("Plan is valid ({} warnings)", warnings);
+        }
+        Command::GenPlan {
+            plan,
+            output,
+            pretty,
+            resolve_monsters,
+        } => {
+            let mut loaded = load_plan(plan)?;
+
+            if resolve_monsters {
+                loaded.plan.resolve_monsters(&mut loaded.seeder);
+            }
+
+            // Never write a plan that `serve --gen` would refuse to load.
+            check_plan(&loaded)?;
+
+            let data = if pretty {
+                serde_json::to_string_pretty(&loaded.plan)?
+            } else {
+                serde_json::to_string(&loaded.plan)?
+            };
+
+            fs::write(&output, data)?;
+            println!("Wrote generation plan to {}", output);
+        }
     }
 
     Ok(())
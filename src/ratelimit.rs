@@ -0,0 +1,210 @@
+use crate::config;
+use crate::data_model;
+
+use std::collections::HashMap;
+use std::net;
+use std::sync::Mutex;
+use std::time;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Movement,
+    Attack,
+    Look,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(net::IpAddr, Action),
+    Player(String, Action),
+}
+
+impl Key {
+    fn action(&self) -> Action {
+        match self {
+            Key::Ip(_, action) | Key::Player(_, action) => *action,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: f64, now: time::Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.updated = now;
+    }
+}
+
+// Token buckets refilled at the configured number of actions per second,
+// holding at most one second worth of actions.
+pub struct RateLimiter {
+    limits: config::RateLimitConfig,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: config::RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn rate(&self, action: Action) -> u32 {
+        match action {
+            Action::Movement => self.limits.movement,
+            Action::Attack => self.limits.attack,
+            Action::Look => self.limits.look,
+        }
+    }
+
+    // Charged before the session is checked, so that guessing tokens is
+    // limited too.
+    pub fn check_ip(&self, ip: net::IpAddr, action: Action) -> Result<(), data_model::WorldError> {
+        self.take(Key::Ip(ip, action), time::Instant::now())
+    }
+
+    // Only charged once the session is known to own the player, otherwise
+    // anyone could drain the bucket of someone else.
+    pub fn check_player(&self, guid: &str, action: Action) -> Result<(), data_model::WorldError> {
+        self.take(Key::Player(guid.to_string(), action), time::Instant::now())
+    }
+
+    fn take(&self, key: Key, now: time::Instant) -> Result<(), data_model::WorldError> {
+        let rate = self.rate(key.action());
+        if rate == 0 {
+            return Ok(());
+        }
+
+        let rate = rate as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        bucket.refill(rate, now);
+
+        if bucket.tokens < 1.0 {
+            return Err(data_model::WorldError::RateLimited);
+        }
+
+        bucket.tokens -= 1.0;
+
+        Ok(())
+    }
+
+    pub fn prune(&self) {
+        self.prune_at(time::Instant::now());
+    }
+
+    // Full buckets behave exactly like missing ones, so they can be dropped.
+    fn prune_at(&self, now: time::Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        buckets.retain(|key, bucket| {
+            let rate = self.rate(key.action()) as f64;
+
+            bucket.refill(rate, now);
+            bucket.tokens < rate
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(config::RateLimitConfig {
+            movement: 2,
+            attack: 0,
+            look: 10,
+        })
+    }
+
+    fn player(action: Action) -> Key {
+        Key::Player(String::from("guid"), action)
+    }
+
+    #[test]
+    fn allows_a_burst_of_one_second() {
+        let limiter = limiter();
+        let now = time::Instant::now();
+
+        assert!(limiter.take(player(Action::Movement), now).is_ok());
+        assert!(limiter.take(player(Action::Movement), now).is_ok());
+        assert!(limiter.take(player(Action::Movement), now).is_err());
+        assert!(limiter.take(player(Action::Look), now).is_ok());
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = limiter();
+        let now = time::Instant::now();
+
+        limiter.take(player(Action::Movement), now).unwrap();
+        limiter.take(player(Action::Movement), now).unwrap();
+
+        let later = now + time::Duration::from_millis(500);
+        assert!(limiter.take(player(Action::Movement), later).is_ok());
+        assert!(limiter.take(player(Action::Movement), later).is_err());
+    }
+
+    #[test]
+    fn caps_the_burst_after_a_long_pause() {
+        let limiter = limiter();
+        let now = time::Instant::now();
+
+        limiter.take(player(Action::Movement), now).unwrap();
+
+        let later = now + time::Duration::from_secs(60);
+        assert!(limiter.take(player(Action::Movement), later).is_ok());
+        assert!(limiter.take(player(Action::Movement), later).is_ok());
+        assert!(limiter.take(player(Action::Movement), later).is_err());
+    }
+
+    #[test]
+    fn a_rate_of_zero_disables_the_limit() {
+        let limiter = limiter();
+        let now = time::Instant::now();
+
+        for _ in 0..100 {
+            assert!(limiter.take(player(Action::Attack), now).is_ok());
+        }
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ip_and_player_buckets_are_separate() {
+        let limiter = limiter();
+        let ip = net::IpAddr::from([127, 0, 0, 1]);
+        let now = time::Instant::now();
+
+        limiter.take(Key::Ip(ip, Action::Movement), now).unwrap();
+        limiter.take(Key::Ip(ip, Action::Movement), now).unwrap();
+
+        assert!(limiter.take(Key::Ip(ip, Action::Movement), now).is_err());
+        assert!(limiter.take(player(Action::Movement), now).is_ok());
+    }
+
+    #[test]
+    fn prunes_only_full_buckets() {
+        let limiter = limiter();
+        let now = time::Instant::now();
+
+        limiter.take(player(Action::Movement), now).unwrap();
+        limiter.take(player(Action::Look), now).unwrap();
+
+        limiter.prune_at(now + time::Duration::from_millis(200));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+
+        limiter.prune_at(now + time::Duration::from_secs(1));
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+}
@@ -1,60 +1,85 @@
-use std::num::ParseIntError;
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
 
 #[derive(Clone, Debug)]
 pub struct Seeder {
-    store: Vec<u8>,
-    cursor: usize,
+    state: u64,
 }
 
 impl Seeder {
-    pub fn try_from_seed(seed: String) -> Result<Seeder, ParseIntError> {
-        let mut store: Vec<u8> = Vec::new();
-        let mut strings: Vec<String> = Vec::new();
-
-        let mut new_string = true;
-        let mut current_string = String::new();
-
-        for (i, c) in seed.chars().enumerate() {
-            if new_string {
-                current_string = String::new();
-                current_string.push(c);
-
-                if i == seed.len() - 1 {
-                    strings.push(current_string.clone());
-                }
-            } else {
-                current_string.push(c);
-                strings.push(current_string.clone());
-            }
+    pub fn from_seed(seed: String) -> Seeder {
+        let mut hash = FNV_OFFSET_BASIS;
+
+        for byte in seed.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+
+        Seeder { state: hash }
+    }
 
-            new_string = !new_string;
+    pub fn random() -> Seeder {
+        Seeder {
+            state: fastrand::u64(..),
         }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
 
-        for s in strings.iter() {
-            store.push(u8::from_str_radix(s.as_str(), 16)?);
+    pub fn seed_u32_bounded(&mut self, lower_bound: u32, upper_bound: u32) -> u32 {
+        let range = (upper_bound - lower_bound) as u64 + 1;
+        let limit = u64::MAX - u64::MAX % range;
+
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return lower_bound + (value % range) as u32;
+            }
         }
+    }
+}
 
-        Ok(Seeder {
-            store: store,
-            cursor: 0,
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(seeder: &mut Seeder, count: usize) -> Vec<u32> {
+        (0..count).map(|_| seeder.seed_u32_bounded(0, 1000)).collect()
     }
 
-    pub fn seed(&mut self) -> u8 {
-        let seed = self.store[self.cursor];
-        self.cursor = (self.cursor + 1) % self.store.len();
+    #[test]
+    fn same_seed_gives_same_values() {
+        let mut first = Seeder::from_seed(String::from("mungeon"));
+        let mut second = Seeder::from_seed(String::from("mungeon"));
 
-        seed
+        assert_eq!(draw(&mut first, 100), draw(&mut second, 100));
     }
 
-    pub fn seed_u32(&mut self) -> u32 {
-        ((self.seed() as u32) << 24)
-            + ((self.seed() as u32) << 16)
-            + ((self.seed() as u32) << 8)
-            + ((self.seed() as u32) << 0)
+    #[test]
+    fn different_seeds_give_different_values() {
+        let mut first = Seeder::from_seed(String::from("mungeon"));
+        let mut second = Seeder::from_seed(String::from("dungeon"));
+
+        assert_ne!(draw(&mut first, 100), draw(&mut second, 100));
     }
 
-    pub fn seed_u32_bounded(&mut self, lower_bound: u32, upper_bound: u32) -> u32 {
-        self.seed_u32() % (upper_bound - lower_bound + 1) + lower_bound
+    #[test]
+    fn values_stay_within_bounds() {
+        let mut seeder = Seeder::from_seed(String::from("bounds"));
+
+        for _ in 0..1000 {
+            let value = seeder.seed_u32_bounded(3, 7);
+            assert!((3..=7).contains(&value));
+        }
+        assert_eq!(seeder.seed_u32_bounded(5, 5), 5);
+        seeder.seed_u32_bounded(0, u32::MAX);
     }
 }
@@ -1,46 +1,237 @@
+use crate::accounts;
+use crate::bans;
+use crate::config;
 use crate::data_model;
+use crate::dump;
+use crate::events;
+use crate::i18n;
+use crate::ratelimit;
+use crate::snapshot;
+use crate::telnet;
 use crate::world;
 
+use rocket::fairing::AdHoc;
+use rocket::http::uri::{Origin, Segments};
+use rocket::http::{ContentType, Cookie, Cookies, Method, SameSite};
+use rocket::request::{self, FromRequest};
+use rocket::response::{content, Stream};
 use rocket_contrib::json::Json;
 
-use std::sync::{Arc, Mutex};
+use std::error;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time;
 
-pub type IpList = Vec<std::net::IpAddr>;
+pub type IpList = Arc<RwLock<bans::BanList>>;
 pub type SharedWorld = Arc<Mutex<world::World>>;
+pub type SharedRateLimiter = Arc<ratelimit::RateLimiter>;
+pub type SharedStreamSlots = Arc<events::StreamSlots>;
 
-fn check_ban(user_ip: std::net::IpAddr, banned_ips: IpList) -> Result<(), data_model::WorldError> {
-    if banned_ips.contains(&user_ip) {
-        Err(data_model::WorldError::Other(String::from(
-            "You have been struck by the holy Banhammer, now get out!",
-        )))
-    } else {
-        Ok(())
+static SESSION_HEADER: &str = "X-Session-Token";
+static SESSION_COOKIE: &str = "session";
+static ADMIN_HEADER: &str = "X-Admin-Token";
+static ACCEPT_LANGUAGE_HEADER: &str = "Accept-Language";
+static BANNED_ROUTE: &str = "/banni";
+
+const LIVE_MAP_TILE_SIZE: u32 = 4;
+const LIVE_MAP_MAX_TILE_SIZE: u32 = 32;
+
+pub struct AdminToken(Option<String>);
+
+// Set when players reach the server over HTTPS, usually through a proxy.
+pub struct SecureCookies(bool);
+
+pub struct Admin;
+
+// Compares every byte so that the time taken does not reveal how much of the
+// admin token was guessed right.
+fn constant_time_eq(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual.iter())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = data_model::WorldError;
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Admin, Self::Error> {
+        let expected = request
+            .guard::<rocket::State<AdminToken>>()
+            .succeeded()
+            .and_then(|admin_token| admin_token.inner().0.clone());
+
+        match (expected, request.headers().get_one(ADMIN_HEADER)) {
+            (Some(expected), Some(token))
+                if constant_time_eq(expected.as_bytes(), token.as_bytes()) =>
+            {
+                rocket::Outcome::Success(Admin)
+            }
+            _ => rocket::Outcome::Failure((
+                rocket::http::Status::Unauthorized,
+                data_model::WorldError::Unauthorized,
+            )),
+        }
+    }
+}
+
+pub struct Session {
+    token: String,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Session {
+    type Error = data_model::WorldError;
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Session, Self::Error> {
+        let token = match request.headers().get_one(SESSION_HEADER) {
+            Some(token) => Some(token.to_string()),
+            None => request
+                .cookies()
+                .get(SESSION_COOKIE)
+                .map(|cookie| cookie.value().to_string()),
+        };
+
+        match token {
+            Some(token) => rocket::Outcome::Success(Session { token }),
+            None => rocket::Outcome::Failure((
+                rocket::http::Status::Unauthorized,
+                data_model::WorldError::Unauthorized,
+            )),
+        }
+    }
+}
+
+// The language given when connecting wins over the Accept-Language header,
+// English is used when neither is available.
+impl<'a, 'r> FromRequest<'a, 'r> for i18n::Lang {
+    type Error = ();
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<i18n::Lang, ()> {
+        let session_lang = request.guard::<Session>().succeeded().and_then(|session| {
+            request
+                .guard::<rocket::State<SharedWorld>>()
+                .succeeded()
+                .and_then(|world| world.lock().unwrap().session_lang(&session.token))
+        });
+
+        let lang = session_lang
+            .or_else(|| {
+                request
+                    .headers()
+                    .get_one(ACCEPT_LANGUAGE_HEADER)
+                    .and_then(i18n::Lang::from_accept_language)
+            })
+            .unwrap_or_default();
+
+        rocket::Outcome::Success(lang)
     }
 }
 
-#[post("/connect")]
+fn add_session_cookie(cookies: &mut Cookies, secure: &SecureCookies, status: &data_model::Status) {
+    cookies.add(
+        Cookie::build(SESSION_COOKIE, status.token.clone())
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .secure(secure.0)
+            .finish(),
+    );
+}
+
+fn remember_lang(
+    world: &mut world::World,
+    status: &data_model::Status,
+    langue: Option<i18n::Lang>,
+) -> Result<(), data_model::WorldError> {
+    match langue {
+        Some(lang) => world.set_lang(status.guid.clone(), lang),
+        None => Ok(()),
+    }
+}
+
+#[post("/connect?<langue>")]
 fn connect(
     world: rocket::State<SharedWorld>,
-    banned_ips: rocket::State<IpList>,
+    mut cookies: Cookies,
+    secure: rocket::State<SecureCookies>,
+    lang: i18n::Lang,
+    langue: Option<i18n::Lang>,
+) -> Result<Json<data_model::Status>, data_model::WorldError> {
+    let mut world = world.lock().unwrap();
+    let status = world.connect(langue.unwrap_or(lang))?;
+    remember_lang(&mut world, &status, langue)?;
+    add_session_cookie(&mut cookies, &secure, &status);
+    Ok(Json(status))
+}
+
+#[post("/register?<langue>", data = "<credentials>")]
+#[allow(clippy::too_many_arguments)]
+fn register(
+    world: rocket::State<SharedWorld>,
+    credentials: Json<data_model::Credentials>,
+    mut cookies: Cookies,
+    secure: rocket::State<SecureCookies>,
+    limiter: rocket::State<SharedRateLimiter>,
     socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+    langue: Option<i18n::Lang>,
 ) -> Result<Json<data_model::Status>, data_model::WorldError> {
-    check_ban(socket_addr.ip(), banned_ips.inner().clone())?;
+    // Hashing passwords is costly, so accounts share the strictest bucket.
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Attack)?;
+    let credentials = credentials.into_inner();
+    let password_hash = accounts::hash_password(credentials.password.as_str())?;
     let mut world = world.lock().unwrap();
-    Ok(Json(world.connect()?))
+    let status = world.register(credentials.username, password_hash, langue.unwrap_or(lang))?;
+    remember_lang(&mut world, &status, langue)?;
+    add_session_cookie(&mut cookies, &secure, &status);
+    Ok(Json(status))
+}
+
+#[post("/login?<langue>", data = "<credentials>")]
+#[allow(clippy::too_many_arguments)]
+fn login(
+    world: rocket::State<SharedWorld>,
+    credentials: Json<data_model::Credentials>,
+    mut cookies: Cookies,
+    secure: rocket::State<SecureCookies>,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+    langue: Option<i18n::Lang>,
+) -> Result<Json<data_model::Status>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Attack)?;
+    let credentials = credentials.into_inner();
+    let password_hash = world
+        .lock()
+        .unwrap()
+        .accounts
+        .password_hash(credentials.username.as_str());
+    accounts::verify_password(credentials.password.as_str(), password_hash)?;
+    let mut world = world.lock().unwrap();
+    let status = world.login(credentials.username, langue.unwrap_or(lang))?;
+    remember_lang(&mut world, &status, langue)?;
+    add_session_cookie(&mut cookies, &secure, &status);
+    Ok(Json(status))
 }
 
 #[get("/<guid>/regarder")]
 fn look_room(
     world: rocket::State<SharedWorld>,
     guid: String,
-    banned_ips: rocket::State<IpList>,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
     socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
 ) -> Result<Json<data_model::Room>, data_model::WorldError> {
-    check_ban(socket_addr.ip(), banned_ips.inner().clone())?;
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Look)?;
     let mut world = world.lock().unwrap();
-    match world.look(guid.clone()) {
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Look)?;
+    match world.look(guid.clone(), lang) {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error.check_not_found(guid)),
     }
@@ -51,12 +242,16 @@ fn movement(
     world: rocket::State<SharedWorld>,
     guid: String,
     req_direction: Json<data_model::ReqDirection>,
-    banned_ips: rocket::State<IpList>,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
     socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
 ) -> Result<Json<data_model::Room>, data_model::WorldError> {
-    check_ban(socket_addr.ip(), banned_ips.inner().clone())?;
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Movement)?;
     let mut world = world.lock().unwrap();
-    match world.r#move(guid.clone(), req_direction.into_inner().direction) {
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Movement)?;
+    match world.r#move(guid.clone(), req_direction.into_inner().direction, lang) {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error.check_not_found(guid)),
     }
@@ -67,12 +262,16 @@ fn look_entity(
     world: rocket::State<SharedWorld>,
     guid: String,
     guid_dest: String,
-    banned_ips: rocket::State<IpList>,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
     socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
 ) -> Result<Json<data_model::Entity>, data_model::WorldError> {
-    check_ban(socket_addr.ip(), banned_ips.inner().clone())?;
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Look)?;
     let mut world = world.lock().unwrap();
-    match world.look_entity(guid.clone(), guid_dest) {
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Look)?;
+    match world.look_entity(guid.clone(), guid_dest, lang) {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error.check_not_found(guid)),
     }
@@ -83,42 +282,488 @@ fn attack(
     world: rocket::State<SharedWorld>,
     guid: String,
     guid_dest: String,
-    banned_ips: rocket::State<IpList>,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
     socket_addr: std::net::SocketAddr,
 ) -> Result<Json<data_model::Fight>, data_model::WorldError> {
-    check_ban(socket_addr.ip(), banned_ips.inner().clone())?;
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Attack)?;
     let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Attack)?;
     match world.attack(guid.clone(), guid_dest) {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error.check_not_found(guid)),
     }
 }
 
+#[get("/<guid>/inventaire")]
+fn inventory(
+    world: rocket::State<SharedWorld>,
+    guid: String,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+) -> Result<Json<data_model::Inventory>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Look)?;
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Look)?;
+    match world.inventory(guid.clone(), lang) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+#[post("/<guid>/ramasser/<item_guid>")]
+fn pick_up(
+    world: rocket::State<SharedWorld>,
+    guid: String,
+    item_guid: String,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+) -> Result<Json<data_model::Inventory>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Movement)?;
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Movement)?;
+    match world.pick_up(guid.clone(), item_guid, lang) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+#[post("/<guid>/lacher/<item_guid>")]
+fn drop_item(
+    world: rocket::State<SharedWorld>,
+    guid: String,
+    item_guid: String,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+) -> Result<Json<data_model::Inventory>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Movement)?;
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Movement)?;
+    match world.drop_item(guid.clone(), item_guid, lang) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+#[post("/<guid>/equiper/<item_guid>")]
+fn equip(
+    world: rocket::State<SharedWorld>,
+    guid: String,
+    item_guid: String,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+) -> Result<Json<data_model::Inventory>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Movement)?;
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Movement)?;
+    match world.equip(guid.clone(), item_guid, lang) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+#[post("/<guid>/utiliser/<item_guid>")]
+fn use_item(
+    world: rocket::State<SharedWorld>,
+    guid: String,
+    item_guid: String,
+    session: Session,
+    limiter: rocket::State<SharedRateLimiter>,
+    socket_addr: std::net::SocketAddr,
+    lang: i18n::Lang,
+) -> Result<Json<data_model::Inventory>, data_model::WorldError> {
+    limiter.check_ip(socket_addr.ip(), ratelimit::Action::Movement)?;
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    limiter.check_player(&guid, ratelimit::Action::Movement)?;
+    match world.use_item(guid.clone(), item_guid, lang) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+#[get("/<guid>/evenements")]
+fn room_events(
+    world: rocket::State<SharedWorld>,
+    slots: rocket::State<SharedStreamSlots>,
+    guid: String,
+    session: Session,
+) -> Result<content::Content<Stream<events::EventStream>>, data_model::WorldError> {
+    let mut world = world.lock().unwrap();
+    world.authorize(&session.token, &guid)?;
+    let slot = slots
+        .acquire()
+        .ok_or(data_model::WorldError::TooManyStreams)?;
+    match world.subscribe(guid.clone()) {
+        Ok(receiver) => Ok(content::Content(
+            ContentType::new("text", "event-stream"),
+            Stream::from(events::EventStream::new(receiver, slot)),
+        )),
+        Err(error) => Err(error.check_not_found(guid)),
+    }
+}
+
+fn live_map_tile_size(taille: Option<u32>) -> u32 {
+    taille
+        .unwrap_or(LIVE_MAP_TILE_SIZE)
+        .clamp(1, LIVE_MAP_MAX_TILE_SIZE)
+}
+
+#[get("/admin/carte.png?<etage>&<taille>")]
+fn live_map_png(
+    world: rocket::State<SharedWorld>,
+    _admin: Admin,
+    etage: Option<isize>,
+    taille: Option<u32>,
+) -> Result<content::Content<Vec<u8>>, data_model::WorldError> {
+    let tiles = world.lock().unwrap().live_tiles(etage);
+    match dump::render_live_png(&tiles, live_map_tile_size(taille)) {
+        Ok(data) => Ok(content::Content(ContentType::PNG, data)),
+        Err(error) => {
+            println!("Could not render map: {}", error);
+            Err(data_model::WorldError::Internal)
+        }
+    }
+}
+
+#[get("/admin/carte.svg?<etage>&<taille>")]
+fn live_map_svg(
+    world: rocket::State<SharedWorld>,
+    _admin: Admin,
+    etage: Option<isize>,
+    taille: Option<u32>,
+) -> Result<content::Content<String>, data_model::WorldError> {
+    let tiles = world.lock().unwrap().live_tiles(etage);
+    match dump::render_live_svg(&tiles, live_map_tile_size(taille)) {
+        Ok(data) => Ok(content::Content(ContentType::SVG, data)),
+        Err(error) => {
+            println!("Could not render map: {}", error);
+            Err(data_model::WorldError::Internal)
+        }
+    }
+}
+
+#[get("/admin/joueurs")]
+fn list_players(
+    world: rocket::State<SharedWorld>,
+    _admin: Admin,
+) -> Result<Json<Vec<data_model::PlayerSummary>>, data_model::WorldError> {
+    Ok(Json(world.lock().unwrap().players()))
+}
+
+#[post("/admin/expulser/<guid>")]
+fn kick_player(
+    guid: String,
+    world: rocket::State<SharedWorld>,
+    _admin: Admin,
+) -> Result<(), data_model::WorldError> {
+    world.lock().unwrap().kick(guid)
+}
+
+#[post("/admin/annonce", data = "<announcement>")]
+fn broadcast(
+    announcement: Json<data_model::Announcement>,
+    world: rocket::State<SharedWorld>,
+    _admin: Admin,
+) -> Result<(), data_model::WorldError> {
+    world
+        .lock()
+        .unwrap()
+        .broadcast(announcement.into_inner().message);
+    Ok(())
+}
+
+#[get("/admin/bannis")]
+fn list_bans(_admin: Admin, banned_ips: rocket::State<IpList>) -> Json<Vec<data_model::Ban>> {
+    Json(
+        banned_ips
+            .read()
+            .unwrap()
+            .entries()
+            .iter()
+            .map(|entry| entry.to_data_model())
+            .collect(),
+    )
+}
+
+fn ban_list_error(error: io::Error) -> data_model::WorldError {
+    println!("Could not save ban list: {}", error);
+
+    data_model::WorldError::Internal
+}
+
+#[post("/admin/bannis", data = "<ban>")]
+fn add_ban(
+    ban: Json<data_model::Ban>,
+    _admin: Admin,
+    banned_ips: rocket::State<IpList>,
+) -> Result<(), data_model::WorldError> {
+    let entry = bans::BanEntry::from_data_model(ban.into_inner())
+        .map_err(data_model::WorldError::BadRequest)?;
+    let network = entry.network;
+    match banned_ips.write().unwrap().ban(entry) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(data_model::WorldError::Other(i18n::Message::AlreadyBanned(
+            network.to_string(),
+        ))),
+        Err(error) => Err(ban_list_error(error)),
+    }
+}
+
+// Takes every remaining segment so that networks such as 10.0.0.0/8 can be
+// unbanned.
+#[delete("/admin/bannis/<ip..>")]
+fn remove_ban(
+    ip: Segments,
+    _admin: Admin,
+    banned_ips: rocket::State<IpList>,
+) -> Result<(), data_model::WorldError> {
+    let ip = ip.collect::<Vec<&str>>().join("/");
+    let network =
+        bans::Network::from_str(ip.as_str()).map_err(data_model::WorldError::BadRequest)?;
+    match banned_ips.write().unwrap().unban(&network) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(data_model::WorldError::Other(i18n::Message::NotBanned(
+            network.to_string(),
+        ))),
+        Err(error) => Err(ban_list_error(error)),
+    }
+}
+
+// Reason of the ban matched by the ban list fairing, cached on the request.
+struct BanReason(Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for BanReason {
+    type Error = ();
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<BanReason, ()> {
+        let reason = request.local_cache(|| BanReason(None));
+
+        rocket::Outcome::Success(BanReason(reason.0.clone()))
+    }
+}
+
+// Banned requests are rewritten to this route by the ban list fairing.
+#[get("/banni")]
+fn banned(reason: BanReason) -> data_model::WorldError {
+    data_model::WorldError::Banned(reason.0)
+}
+
+fn ban_fairing() -> AdHoc {
+    AdHoc::on_request("Ban list", |request, _| {
+        // Only the socket address can be trusted, headers such as X-Real-IP
+        // are set by the client.
+        let reason = match (
+            request.remote(),
+            request.guard::<rocket::State<IpList>>().succeeded(),
+        ) {
+            (Some(remote), Some(banned_ips)) => banned_ips
+                .read()
+                .unwrap()
+                .find(&remote.ip())
+                .map(|entry| entry.reason.clone()),
+            _ => None,
+        };
+
+        if let Some(reason) = reason {
+            request.local_cache(|| BanReason(reason));
+            request.set_method(Method::Get);
+            request.set_uri(Origin::parse(BANNED_ROUTE).unwrap());
+        }
+    })
+}
+
+#[catch(400)]
+fn bad_request() -> data_model::WorldError {
+    data_model::WorldError::BadRequest(i18n::Message::BadRequest)
+}
+
+#[catch(401)]
+fn unauthorized() -> data_model::WorldError {
+    data_model::WorldError::Unauthorized
+}
+
+#[catch(404)]
+fn not_found() -> data_model::WorldError {
+    data_model::WorldError::NotFound
+}
+
+#[catch(422)]
+fn unprocessable() -> data_model::WorldError {
+    data_model::WorldError::Unprocessable
+}
+
+#[catch(500)]
+fn internal_error() -> data_model::WorldError {
+    data_model::WorldError::Internal
+}
+
 fn spawn_afk_thread(world: SharedWorld) {
     let check_rate = time::Duration::from_secs(5);
 
     thread::spawn(move || {
         loop {
             thread::sleep(check_rate);
-            world.lock().unwrap().disconnect_afk_players().unwrap();
+            if let Err(error) = world.lock().unwrap().disconnect_afk_players() {
+                println!("Could not disconnect AFK players: {}", error.message());
+            }
+        }
+    });
+}
+
+fn spawn_ban_reload_thread(banned_ips: IpList) {
+    let check_rate = time::Duration::from_secs(5);
+
+    thread::spawn(move || loop {
+        thread::sleep(check_rate);
+        match banned_ips.write().unwrap().reload_if_changed() {
+            Ok(true) => println!("Reloaded ban list"),
+            Ok(false) => (),
+            Err(error) => println!("Could not reload ban list: {}", error),
+        }
+    });
+}
+
+fn spawn_rate_limit_prune_thread(limiter: SharedRateLimiter) {
+    let prune_rate = time::Duration::from_secs(60);
+
+    thread::spawn(move || loop {
+        thread::sleep(prune_rate);
+        limiter.prune();
+    });
+}
+
+fn spawn_respawn_thread(world: SharedWorld) {
+    let check_rate = time::Duration::from_secs(5);
+
+    thread::spawn(move || loop {
+        thread::sleep(check_rate);
+        world.lock().unwrap().respawn_monsters();
+    });
+}
+
+fn spawn_monster_thread(world: SharedWorld) {
+    let tick_rate = time::Duration::from_secs(2);
+
+    thread::spawn(move || loop {
+        thread::sleep(tick_rate);
+        world.lock().unwrap().tick_monsters();
+    });
+}
+
+fn spawn_autosave_thread(world: SharedWorld, save_path: String) {
+    let save_rate = time::Duration::from_secs(60);
+
+    thread::spawn(move || loop {
+        thread::sleep(save_rate);
+        let world = {
+            let mut world = world.lock().unwrap();
+            world.save_accounts();
+            world.clone()
+        };
+        match snapshot::save(&world, save_path.as_str()) {
+            Ok(()) => (),
+            Err(error) => println!("Could not save world to {}: {}", save_path, error),
         }
     });
 }
 
-pub fn launch(world: world::World, banned_ips: IpList) -> Result<(), std::net::AddrParseError> {
+pub fn launch(
+    world: world::World,
+    banned_ips: bans::BanList,
+    config: config::Config,
+) -> Result<(), Box<dyn error::Error>> {
     let world = Arc::new(Mutex::new(world));
+    let banned_ips: IpList = Arc::new(RwLock::new(banned_ips));
+    let limiter: SharedRateLimiter = Arc::new(ratelimit::RateLimiter::new(config.rate_limits));
+    let stream_slots: SharedStreamSlots =
+        Arc::new(events::StreamSlots::new(config.server.max_streams));
 
     spawn_afk_thread(Arc::clone(&world));
+    spawn_respawn_thread(Arc::clone(&world));
+    spawn_ban_reload_thread(Arc::clone(&banned_ips));
+    spawn_rate_limit_prune_thread(Arc::clone(&limiter));
+    spawn_monster_thread(Arc::clone(&world));
+    spawn_autosave_thread(Arc::clone(&world), config.paths.save);
+
+    if let Some(address) = config.server.telnet {
+        telnet::spawn_listener(
+            Arc::clone(&world),
+            banned_ips.clone(),
+            Arc::clone(&limiter),
+            address,
+        )?;
+    }
+
+    for entry in banned_ips.read().unwrap().entries().iter() {
+        println!("Banned: {}", entry);
+    }
+
+    if config.admin.token.is_none() {
+        println!("No admin token configured, admin routes are disabled");
+    }
 
-    println!("{:?}", banned_ips);
+    let rocket_config = rocket::Config::build(rocket::config::Environment::active()?)
+        .address(config.server.address)
+        .port(config.server.port)
+        .workers(config.server.workers)
+        .finalize()?;
 
-    rocket::ignite()
+    rocket::custom(rocket_config)
         .manage(world)
         .manage(banned_ips)
+        .manage(limiter)
+        .manage(stream_slots)
+        .manage(AdminToken(config.admin.token))
+        .manage(SecureCookies(config.server.secure_cookies))
+        .attach(ban_fairing())
         .mount(
             "/",
-            routes![connect, look_room, movement, look_entity, attack],
+            routes![
+                connect,
+                register,
+                login,
+                look_room,
+                movement,
+                look_entity,
+                attack,
+                inventory,
+                pick_up,
+                drop_item,
+                equip,
+                use_item,
+                room_events,
+                live_map_png,
+                live_map_svg,
+                list_players,
+                kick_player,
+                broadcast,
+                list_bans,
+                add_ban,
+                remove_ban,
+                banned
+            ],
         )
+        .register(catchers![
+            bad_request,
+            unauthorized,
+            not_found,
+            unprocessable,
+            internal_error
+        ])
         .launch();
 
     Ok(())
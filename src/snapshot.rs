@@ -0,0 +1,20 @@
+use crate::world;
+
+use std::error;
+use std::fs;
+use std::io;
+
+pub fn save(world: &world::World, path: &str) -> io::Result<()> {
+    let data = serde_json::to_string(world)?;
+    let tmp_path = format!("{}.tmp", path);
+
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)
+}
+
+pub fn load(path: &str) -> Result<world::World, Box<dyn error::Error>> {
+    let data = fs::read_to_string(path)?;
+    let world: world::World = serde_json::from_str(data.as_str())?;
+
+    Ok(world)
+}
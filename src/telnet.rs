@@ -0,0 +1,586 @@
+use crate::data_model;
+use crate::i18n;
+use crate::ratelimit;
+use crate::server::{IpList, SharedRateLimiter, SharedWorld};
+
+use std::io::{self, BufRead, Read, Write};
+use std::net::{self, Shutdown, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+const SHORT_ID_LENGTH: usize = 8;
+const MAX_LINE_LENGTH: usize = 256;
+
+type SharedStream = Arc<Mutex<TcpStream>>;
+type SharedLang = Arc<Mutex<i18n::Lang>>;
+
+enum Command {
+    Look,
+    Go(data_model::Direction),
+    Examine(String),
+    Hit(String),
+    Inventory,
+    Take(String),
+    Drop(String),
+    Equip(String),
+    Use(String),
+    Lang(i18n::Lang),
+    Help,
+    Quit,
+    Unknown,
+}
+
+impl Command {
+    fn action(&self) -> Option<ratelimit::Action> {
+        match self {
+            Command::Look | Command::Examine(_) | Command::Inventory => {
+                Some(ratelimit::Action::Look)
+            }
+            Command::Go(_)
+            | Command::Take(_)
+            | Command::Drop(_)
+            | Command::Equip(_)
+            | Command::Use(_) => Some(ratelimit::Action::Movement),
+            Command::Hit(_) => Some(ratelimit::Action::Attack),
+            _ => None,
+        }
+    }
+
+    fn parse(line: &str) -> Command {
+        let mut words = line.split_whitespace();
+        let verb = words.next().unwrap_or("").to_lowercase();
+        let argument = words.next().map(|word| word.to_lowercase());
+
+        match (verb.as_str(), argument) {
+            ("look", None) | ("l", None) => Command::Look,
+            ("go", Some(direction)) => match parse_direction(direction.as_str()) {
+                Some(direction) => Command::Go(direction),
+                None => Command::Unknown,
+            },
+            ("examine", Some(target)) | ("x", Some(target)) => Command::Examine(target),
+            ("hit", Some(target)) | ("attack", Some(target)) => Command::Hit(target),
+            ("inventory", None) | ("i", None) => Command::Inventory,
+            ("take", Some(item)) | ("get", Some(item)) => Command::Take(item),
+            ("drop", Some(item)) => Command::Drop(item),
+            ("equip", Some(item)) | ("wield", Some(item)) => Command::Equip(item),
+            ("use", Some(item)) | ("drink", Some(item)) => Command::Use(item),
+            ("lang", Some(lang)) => match i18n::Lang::from_str(lang.as_str()) {
+                Ok(lang) => Command::Lang(lang),
+                Err(_) => Command::Unknown,
+            },
+            ("help", None) => Command::Help,
+            ("quit", None) | ("exit", None) => Command::Quit,
+            (verb, None) => match parse_direction(verb) {
+                Some(direction) => Command::Go(direction),
+                None => Command::Unknown,
+            },
+            _ => Command::Unknown,
+        }
+    }
+}
+
+fn parse_direction(word: &str) -> Option<data_model::Direction> {
+    match word {
+        "n" | "north" => Some(data_model::Direction::N),
+        "e" | "east" => Some(data_model::Direction::E),
+        "s" | "south" => Some(data_model::Direction::S),
+        "w" | "west" => Some(data_model::Direction::W),
+        "u" | "up" => Some(data_model::Direction::U),
+        "d" | "down" => Some(data_model::Direction::D),
+        _ => None,
+    }
+}
+
+fn short_id(guid: &str) -> &str {
+    &guid[..std::cmp::min(SHORT_ID_LENGTH, guid.len())]
+}
+
+fn format_direction(direction: &data_model::Direction, lang: i18n::Lang) -> String {
+    i18n::Message::DirectionName(direction.clone()).translate(lang)
+}
+
+fn format_room(room: &data_model::Room, own_guid: &str, lang: i18n::Lang) -> String {
+    let exits: Vec<String> = room
+        .paths
+        .iter()
+        .map(|direction| format_direction(direction, lang))
+        .collect();
+    let entities: Vec<String> = room
+        .entities
+        .iter()
+        .map(|guid| {
+            if guid == own_guid {
+                i18n::Message::Yourself(short_id(guid).to_string()).translate(lang)
+            } else {
+                short_id(guid).to_string()
+            }
+        })
+        .collect();
+    let exits = if exits.is_empty() {
+        i18n::Message::NoExits.translate(lang)
+    } else {
+        exits.join(", ")
+    };
+
+    let mut text = format!(
+        "{}\r\n{}\r\n{}",
+        room.description,
+        i18n::Message::Exits(exits).translate(lang),
+        i18n::Message::Occupants(entities.join(", ")).translate(lang)
+    );
+
+    for item in room.items.iter() {
+        text.push_str("\r\n");
+        text.push_str(&i18n::Message::OnTheFloor(format_item(item, lang)).translate(lang));
+    }
+
+    text
+}
+
+fn format_item(item: &data_model::Item, lang: i18n::Lang) -> String {
+    let effect = match item.r#type {
+        data_model::ItemType::Weapon => i18n::Message::WeaponBonus(item.value),
+        data_model::ItemType::Armour => i18n::Message::ArmourBonus(item.value),
+        data_model::ItemType::Potion => i18n::Message::PotionHeal(item.value),
+        data_model::ItemType::Key => i18n::Message::OpensDoors,
+    };
+
+    format!(
+        "{} {} ({})",
+        short_id(&item.guid),
+        item.description,
+        effect.translate(lang)
+    )
+}
+
+fn format_inventory(inventory: &data_model::Inventory, lang: i18n::Lang) -> String {
+    let mut text = i18n::Message::InventoryStats {
+        life: inventory.life,
+        total_life: inventory.total_life,
+        dp: inventory.dp,
+    }
+    .translate(lang);
+
+    for item in inventory.items.iter() {
+        let equipped = inventory.weapon.as_ref() == Some(&item.guid)
+            || inventory.armour.as_ref() == Some(&item.guid);
+
+        text.push_str("\r\n");
+        text.push_str(&format_item(item, lang));
+        if equipped {
+            text.push(' ');
+            text.push_str(&i18n::Message::Equipped.translate(lang));
+        }
+    }
+
+    text
+}
+
+fn format_entity(entity: &data_model::Entity, lang: i18n::Lang) -> String {
+    i18n::Message::EntityStats {
+        description: entity.description.clone(),
+        level: entity.level,
+        life: entity.life,
+        total_life: entity.total_life,
+    }
+    .translate(lang)
+}
+
+fn format_fight(fight: &data_model::Fight, lang: i18n::Lang) -> String {
+    i18n::Message::FightReport {
+        attacker: short_id(&fight.attacker.guid).to_string(),
+        defender: short_id(&fight.defender.guid).to_string(),
+        damage: fight.attacker.dp,
+        taken: fight.defender.dp,
+        attacker_hp: fight.attacker.hp,
+        defender_hp: fight.defender.hp,
+    }
+    .translate(lang)
+}
+
+fn format_event(event: &data_model::RoomEvent, lang: i18n::Lang) -> String {
+    let message = match event {
+        data_model::RoomEvent::Connected { guid } => {
+            i18n::Message::Appeared(short_id(guid).to_string())
+        }
+        data_model::RoomEvent::Disconnected { guid } => {
+            i18n::Message::Vanished(short_id(guid).to_string())
+        }
+        data_model::RoomEvent::Entered { guid } => {
+            i18n::Message::Entered(short_id(guid).to_string())
+        }
+        data_model::RoomEvent::Left { guid, direction } => i18n::Message::Left {
+            guid: short_id(guid).to_string(),
+            direction: direction.clone(),
+        },
+        data_model::RoomEvent::Attacked { fight } => return format_fight(fight, lang),
+        data_model::RoomEvent::Died { guid } => i18n::Message::Died(short_id(guid).to_string()),
+        data_model::RoomEvent::Spawned { guid } => {
+            i18n::Message::Spawned(short_id(guid).to_string())
+        }
+        data_model::RoomEvent::LevelUp { guid, level } => i18n::Message::ReachedLevel {
+            guid: short_id(guid).to_string(),
+            level: *level,
+        },
+        data_model::RoomEvent::Announced { message } => {
+            i18n::Message::Announcement(message.clone())
+        }
+    };
+
+    message.translate(lang)
+}
+
+fn write_line(stream: &SharedStream, line: &str) -> io::Result<()> {
+    let mut stream = stream.lock().unwrap();
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\r\n")?;
+    stream.flush()
+}
+
+fn resolve_target(world: &SharedWorld, guid: &str, prefix: &str) -> Option<String> {
+    let world = world.lock().unwrap();
+
+    world
+        .current_room(guid)?
+        .guids
+        .iter()
+        .find(|entity_guid| entity_guid.starts_with(prefix))
+        .cloned()
+}
+
+fn resolve_item(world: &SharedWorld, guid: &str, prefix: &str, on_floor: bool) -> Option<String> {
+    let world = world.lock().unwrap();
+    let items = if on_floor {
+        &world.current_room(guid)?.items
+    } else {
+        &world.entities.get(guid)?.inventory
+    };
+
+    items
+        .iter()
+        .map(|item| item.guid.clone())
+        .find(|item_guid| item_guid.starts_with(prefix))
+}
+
+// Reads up to the next newline, or returns `None` at the end of the stream.
+// Longer lines are an error so that a client cannot make the buffer grow
+// without bound.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let limit = MAX_LINE_LENGTH as u64 + 1;
+
+    Read::take(reader, limit).read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        Ok(None)
+    } else if line.len() > MAX_LINE_LENGTH && line.last() != Some(&b'\n') {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"))
+    } else {
+        Ok(Some(line))
+    }
+}
+
+fn clean_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .collect()
+}
+
+fn run_command(
+    world: &SharedWorld,
+    limiter: &SharedRateLimiter,
+    ip: net::IpAddr,
+    guid: &str,
+    lang: i18n::Lang,
+    command: Command,
+) -> Result<String, data_model::WorldError> {
+    if let Some(action) = command.action() {
+        limiter.check_ip(ip, action)?;
+        limiter.check_player(guid, action)?;
+    }
+
+    match command {
+        Command::Look => {
+            let room = world.lock().unwrap().look(guid.to_string(), lang)?;
+            Ok(format_room(&room, guid, lang))
+        }
+        Command::Go(direction) => {
+            let room = world
+                .lock()
+                .unwrap()
+                .r#move(guid.to_string(), direction, lang)?;
+            Ok(format_room(&room, guid, lang))
+        }
+        Command::Examine(prefix) => match resolve_target(world, guid, prefix.as_str()) {
+            Some(target) => {
+                let entity = world
+                    .lock()
+                    .unwrap()
+                    .look_entity(guid.to_string(), target, lang)?;
+                Ok(format_entity(&entity, lang))
+            }
+            None => Ok(i18n::Message::UnknownTarget.translate(lang)),
+        },
+        Command::Hit(prefix) => match resolve_target(world, guid, prefix.as_str()) {
+            Some(target) => {
+                let fight = world.lock().unwrap().attack(guid.to_string(), target)?;
+                Ok(format_fight(&fight, lang))
+            }
+            None => Ok(i18n::Message::UnknownTarget.translate(lang)),
+        },
+        Command::Inventory => {
+            let inventory = world.lock().unwrap().inventory(guid.to_string(), lang)?;
+            Ok(format_inventory(&inventory, lang))
+        }
+        Command::Take(prefix) => match resolve_item(world, guid, prefix.as_str(), true) {
+            Some(item) => {
+                let inventory = world
+                    .lock()
+                    .unwrap()
+                    .pick_up(guid.to_string(), item, lang)?;
+                Ok(format_inventory(&inventory, lang))
+            }
+            None => Ok(i18n::Message::UnknownItem.translate(lang)),
+        },
+        Command::Drop(prefix) => match resolve_item(world, guid, prefix.as_str(), false) {
+            Some(item) => {
+                let inventory = world
+                    .lock()
+                    .unwrap()
+                    .drop_item(guid.to_string(), item, lang)?;
+                Ok(format_inventory(&inventory, lang))
+            }
+            None => Ok(i18n::Message::UnknownItem.translate(lang)),
+        },
+        Command::Equip(prefix) => match resolve_item(world, guid, prefix.as_str(), false) {
+            Some(item) => {
+                let inventory = world.lock().unwrap().equip(guid.to_string(), item, lang)?;
+                Ok(format_inventory(&inventory, lang))
+            }
+            None => Ok(i18n::Message::UnknownItem.translate(lang)),
+        },
+        Command::Use(prefix) => match resolve_item(world, guid, prefix.as_str(), false) {
+            Some(item) => {
+                let inventory = world
+                    .lock()
+                    .unwrap()
+                    .use_item(guid.to_string(), item, lang)?;
+                Ok(format_inventory(&inventory, lang))
+            }
+            None => Ok(i18n::Message::UnknownItem.translate(lang)),
+        },
+        Command::Lang(lang) => {
+            world.lock().unwrap().set_lang(guid.to_string(), lang)?;
+            Ok(i18n::Message::LanguageChanged.translate(lang))
+        }
+        Command::Help => Ok(i18n::Message::TelnetHelp.translate(lang)),
+        Command::Quit | Command::Unknown => Ok(i18n::Message::UnknownCommand.translate(lang)),
+    }
+}
+
+fn caused_by(event: &data_model::RoomEvent, guid: &str) -> bool {
+    match event {
+        data_model::RoomEvent::Connected { guid: actor }
+        | data_model::RoomEvent::Disconnected { guid: actor }
+        | data_model::RoomEvent::Entered { guid: actor }
+        | data_model::RoomEvent::Left { guid: actor, .. } => actor == guid,
+        data_model::RoomEvent::Died { .. } => false,
+        data_model::RoomEvent::Spawned { .. } => false,
+        data_model::RoomEvent::LevelUp { .. } => false,
+        data_model::RoomEvent::Announced { .. } => false,
+        data_model::RoomEvent::Attacked { fight } => fight.attacker.guid == guid,
+    }
+}
+
+fn spawn_event_writer(
+    stream: SharedStream,
+    guid: String,
+    lang: SharedLang,
+    receiver: mpsc::Receiver<data_model::RoomEvent>,
+) {
+    thread::spawn(move || {
+        for event in receiver.iter() {
+            if caused_by(&event, guid.as_str()) {
+                continue;
+            }
+
+            let lang = *lang.lock().unwrap();
+            if write_line(&stream, format_event(&event, lang).as_str()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn handle_client(
+    stream: TcpStream,
+    world: SharedWorld,
+    banned_ips: IpList,
+    limiter: SharedRateLimiter,
+) -> io::Result<()> {
+    let mut reader = io::BufReader::new(stream.try_clone()?);
+    let writer: SharedStream = Arc::new(Mutex::new(stream.try_clone()?));
+    let ip = stream.peer_addr()?.ip();
+
+    if let Some(entry) = banned_ips.read().unwrap().find(&ip) {
+        let error = data_model::WorldError::Banned(entry.reason.clone());
+        return write_line(&writer, error.message().as_str());
+    }
+
+    // Commands are typed in English, everything written back follows the
+    // `lang` command.
+    let mut lang = i18n::Lang::default();
+    let shared_lang: SharedLang = Arc::new(Mutex::new(lang));
+
+    let (status, receiver) = {
+        let mut world = world.lock().unwrap();
+        match world.connect(lang) {
+            Ok(status) => {
+                let receiver = world.subscribe(status.guid.clone());
+                (status, receiver)
+            }
+            Err(error) => return write_line(&writer, error.message().as_str()),
+        }
+    };
+    let guid = status.guid;
+
+    write_line(
+        &writer,
+        i18n::Message::Welcome(short_id(&guid).to_string())
+            .translate(lang)
+            .as_str(),
+    )?;
+    write_line(&writer, i18n::Message::TelnetHelp.translate(lang).as_str())?;
+    write_line(&writer, format_room(&status.room, &guid, lang).as_str())?;
+
+    if let Ok(receiver) = receiver {
+        spawn_event_writer(
+            Arc::clone(&writer),
+            guid.clone(),
+            Arc::clone(&shared_lang),
+            receiver,
+        );
+    }
+
+    loop {
+        let line = match read_line(&mut reader) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(ref error) if error.kind() == io::ErrorKind::InvalidData => {
+                let error = i18n::Message::LineTooLong(MAX_LINE_LENGTH);
+                write_line(&writer, error.translate(lang).as_str())?;
+                break;
+            }
+            Err(error) => return Err(error),
+        };
+        let command = Command::parse(clean_line(&line).as_str());
+
+        if let Command::Quit = command {
+            break;
+        }
+
+        if let Command::Lang(new_lang) = command {
+            lang = new_lang;
+            *shared_lang.lock().unwrap() = lang;
+        }
+
+        match run_command(&world, &limiter, ip, &guid, lang, command) {
+            Ok(output) => write_line(&writer, output.as_str())?,
+            Err(error) => {
+                let error = error.check_not_found(guid.clone());
+                write_line(&writer, error.message_in(lang).as_str())?;
+
+                if let data_model::WorldError::Disappeared = error {
+                    break;
+                }
+            }
+        }
+    }
+
+    world.lock().unwrap().disconnect(guid).ok();
+    stream.shutdown(Shutdown::Both).ok();
+
+    Ok(())
+}
+
+pub fn spawn_listener(
+    world: SharedWorld,
+    banned_ips: IpList,
+    limiter: SharedRateLimiter,
+    address: String,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(address.as_str())?;
+
+    println!("Telnet interface listening on {}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let world = Arc::clone(&world);
+                    let banned_ips = banned_ips.clone();
+                    let limiter = Arc::clone(&limiter);
+
+                    thread::spawn(move || {
+                        if let Err(error) = handle_client(stream, world, banned_ips, limiter) {
+                            println!("Telnet client error: {}", error);
+                        }
+                    });
+                }
+                Err(error) => println!("Telnet connection failed: {}", error),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlong_lines_are_rejected() {
+        let mut input = io::Cursor::new(format!("look\n{}\n", "x".repeat(1000)));
+
+        assert_eq!(read_line(&mut input).unwrap(), Some(b"look\n".to_vec()));
+        assert_eq!(
+            read_line(&mut input).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn the_last_line_may_lack_a_newline() {
+        let mut input = io::Cursor::new("quit");
+
+        assert_eq!(read_line(&mut input).unwrap(), Some(b"quit".to_vec()));
+        assert_eq!(read_line(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn rooms_are_described_in_french() {
+        let room = data_model::Room {
+            description: String::from("Une salle"),
+            paths: vec![data_model::Direction::N, data_model::Direction::U],
+            entities: vec![String::from("0123456789"), String::from("abcdefghij")],
+            items: vec![data_model::Item {
+                guid: String::from("potion-guid"),
+                description: String::from("Une potion de soin"),
+                r#type: data_model::ItemType::Potion,
+                value: 30,
+            }],
+            announcements: Vec::new(),
+        };
+
+        assert_eq!(
+            format_room(&room, "0123456789", i18n::Lang::Fr),
+            "Une salle\r\n\
+             Sorties : nord, haut\r\n\
+             Ici : 01234567 (vous), abcdefgh\r\n\
+             Au sol : potion-g Une potion de soin (soigne 30 PV)"
+        );
+    }
+}
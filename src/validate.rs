@@ -0,0 +1,521 @@
+use crate::data_model;
+use crate::gen_plan;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+static DIRECTIONS: [data_model::Direction; 6] = [
+    data_model::Direction::N,
+    data_model::Direction::S,
+    data_model::Direction::E,
+    data_model::Direction::W,
+    data_model::Direction::U,
+    data_model::Direction::D,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        match self.line {
+            Some(line) => write!(f, "{} (line {}): {}", severity, line, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+struct Validator<'a> {
+    plan: &'a gen_plan::WorldPlan,
+    lines: Vec<usize>,
+    issues: Vec<Issue>,
+}
+
+impl<'a> Validator<'a> {
+    fn line(&self, index: usize) -> Option<usize> {
+        self.lines.get(index).cloned()
+    }
+
+    fn report(&mut self, severity: Severity, index: Option<usize>, message: String) {
+        let line = index.and_then(|index| self.line(index));
+
+        self.issues.push(Issue {
+            severity,
+            line,
+            message,
+        });
+    }
+
+    fn index_rooms(&mut self) -> HashMap<gen_plan::Position, usize> {
+        let mut positions: HashMap<gen_plan::Position, usize> = HashMap::new();
+
+        for (index, room) in self.plan.rooms.iter().enumerate() {
+            let position = (room.x, room.y, room.z);
+
+            match positions.get(&position).cloned() {
+                Some(first) => {
+                    let first_line = match self.line(first) {
+                        Some(line) => format!(" on line {}", line),
+                        None => format!(" as room #{}", first),
+                    };
+
+                    self.report(
+                        Severity::Error,
+                        Some(index),
+                        format!(
+                            "Room at {} was already declared{} and would be overwritten",
+                            format_position(position),
+                            first_line
+                        ),
+                    );
+                }
+                None => {
+                    positions.insert(position, index);
+                }
+            }
+        }
+
+        positions
+    }
+
+    fn check_spawn(&mut self, positions: &HashMap<gen_plan::Position, usize>) -> Option<usize> {
+        let spawn = (self.plan.spawn_x, self.plan.spawn_y, self.plan.spawn_z);
+
+        match positions.get(&spawn) {
+            Some(index) => Some(*index),
+            None => {
+                self.report(
+                    Severity::Error,
+                    None,
+                    format!(
+                        "Spawn {} is not inside any room, players could never connect",
+                        format_position(spawn)
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn check_rooms(&mut self, positions: &HashMap<gen_plan::Position, usize>) {
+        let mut keys: HashSet<String> = HashSet::new();
+
+        for room in self.plan.rooms.iter() {
+            for item in room.items.iter().flatten() {
+                if let gen_plan::ItemKind::Key { key } = &item.kind {
+                    keys.insert(key.clone());
+                }
+            }
+
+            if let Some(gen_plan::MonstersPlan::Defined(monsters)) = &room.monsters {
+                for monster in monsters.iter() {
+                    for item in monster.loot.iter().flatten() {
+                        if let gen_plan::ItemKind::Key { key } = &item.kind {
+                            keys.insert(key.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        for (index, room) in self.plan.rooms.iter().enumerate() {
+            let position = (room.x, room.y, room.z);
+
+            if let Some(gen_plan::MonstersPlan::Defined(monsters)) = &room.monsters {
+                for (monster_index, monster) in monsters.iter().enumerate() {
+                    if monster.hp == 0 {
+                        self.report(
+                            Severity::Error,
+                            Some(index),
+                            format!(
+                                "Monster #{} in room {} has 0 HP",
+                                monster_index,
+                                format_position(position)
+                            ),
+                        );
+                    }
+                }
+            }
+
+            let mut declared: Vec<&data_model::Direction> = Vec::new();
+
+            for exit in room.exits.iter().flatten() {
+                if declared.contains(&&exit.direction) {
+                    self.report(
+                        Severity::Warning,
+                        Some(index),
+                        format!(
+                            "Room {} declares its {:?} exit more than once",
+                            format_position(position),
+                            exit.direction
+                        ),
+                    );
+                }
+                declared.push(&exit.direction);
+
+                let neighbour = gen_plan::step(position, &exit.direction);
+
+                match positions.get(&neighbour) {
+                    None => self.report(
+                        Severity::Warning,
+                        Some(index),
+                        format!(
+                            "Exit {:?} of room {} leads to {} where there is no room",
+                            exit.direction,
+                            format_position(position),
+                            format_position(neighbour)
+                        ),
+                    ),
+                    Some(neighbour_index) => {
+                        let next_room = &self.plan.rooms[*neighbour_index];
+
+                        if !gen_plan::allows(
+                            &next_room.exits,
+                            &next_room.stairs,
+                            &exit.direction.opposite(),
+                        ) {
+                            self.report(
+                                Severity::Warning,
+                                Some(index),
+                                format!(
+                                    "Exit {:?} of room {} is never usable because room {} does not open back {:?}",
+                                    exit.direction,
+                                    format_position(position),
+                                    format_position(neighbour),
+                                    exit.direction.opposite()
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                if let Some(gen_plan::Lock::Key { key }) = &exit.lock {
+                    if !keys.contains(key) {
+                        self.report(
+                            Severity::Warning,
+                            Some(index),
+                            format!(
+                                "Exit {:?} of room {} needs the {} key, which is never placed in the world",
+                                exit.direction,
+                                format_position(position),
+                                key
+                            ),
+                        );
+                    }
+                }
+            }
+
+            if let Some(stairs) = room.stairs {
+                for (goes, direction) in [
+                    (stairs.goes_up(), data_model::Direction::U),
+                    (stairs.goes_down(), data_model::Direction::D),
+                ]
+                .iter()
+                {
+                    if !goes {
+                        continue;
+                    }
+
+                    let neighbour = gen_plan::step(position, direction);
+                    let connected = match positions.get(&neighbour) {
+                        Some(neighbour_index) => {
+                            let next_room = &self.plan.rooms[*neighbour_index];
+
+                            gen_plan::allows(
+                                &next_room.exits,
+                                &next_room.stairs,
+                                &direction.opposite(),
+                            )
+                        }
+                        None => false,
+                    };
+
+                    if !connected {
+                        self.report(
+                            Severity::Warning,
+                            Some(index),
+                            format!(
+                                "Stairs going {:?} in room {} do not reach matching stairs at {}",
+                                direction,
+                                format_position(position),
+                                format_position(neighbour)
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_connectivity(&mut self, positions: &HashMap<gen_plan::Position, usize>, spawn: usize) {
+        let mut region_of: HashMap<gen_plan::Position, usize> = HashMap::new();
+        let mut regions: Vec<(usize, usize)> = Vec::new();
+
+        let mut starts: Vec<usize> = vec![spawn];
+        starts.extend(0..self.plan.rooms.len());
+
+        for start in starts.into_iter() {
+            let room = &self.plan.rooms[start];
+            let start_position = (room.x, room.y, room.z);
+
+            if region_of.contains_key(&start_position) {
+                continue;
+            }
+
+            let region = regions.len();
+            let mut size = 0;
+            let mut queue = VecDeque::new();
+            region_of.insert(start_position, region);
+            queue.push_back(start_position);
+
+            while let Some(position) = queue.pop_front() {
+                size += 1;
+                let room = &self.plan.rooms[positions[&position]];
+
+                for direction in DIRECTIONS.iter() {
+                    let neighbour = gen_plan::step(position, direction);
+
+                    if region_of.contains_key(&neighbour) {
+                        continue;
+                    }
+
+                    if let Some(neighbour_index) = positions.get(&neighbour) {
+                        let next_room = &self.plan.rooms[*neighbour_index];
+
+                        if gen_plan::allows(&room.exits, &room.stairs, direction)
+                            && gen_plan::allows(
+                                &next_room.exits,
+                                &next_room.stairs,
+                                &direction.opposite(),
+                            )
+                        {
+                            region_of.insert(neighbour, region);
+                            queue.push_back(neighbour);
+                        }
+                    }
+                }
+            }
+
+            regions.push((start, size));
+        }
+
+        for (first, size) in regions.into_iter().skip(1) {
+            let room = &self.plan.rooms[first];
+
+            self.report(
+                Severity::Warning,
+                Some(first),
+                format!(
+                    "{} {} around {} cannot be reached from the spawn",
+                    size,
+                    if size > 1 { "rooms" } else { "room" },
+                    format_position((room.x, room.y, room.z))
+                ),
+            );
+        }
+    }
+}
+
+fn format_position(position: gen_plan::Position) -> String {
+    if position.2 == 0 {
+        format!("({},{})", position.0, position.1)
+    } else {
+        format!("({},{}) on floor {}", position.0, position.1, position.2)
+    }
+}
+
+// Finds the line where each element of the top level "rooms" array starts,
+// since serde does not keep track of positions once the plan is parsed.
+fn room_lines(source: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut line = 1;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut string = String::new();
+    let mut last_key = String::new();
+    let mut in_rooms = false;
+
+    for c in source.chars() {
+        if c == '\n' {
+            line += 1;
+        }
+
+        if in_string {
+            if escaped {
+                escaped = false;
+                string.push(c);
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+                if depth == 1 {
+                    last_key = string.clone();
+                }
+            } else {
+                string.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                string.clear();
+            }
+            '[' | '{' => {
+                if c == '[' && depth == 1 && last_key == "rooms" {
+                    in_rooms = true;
+                } else if c == '{' && depth == 2 && in_rooms {
+                    lines.push(line);
+                }
+                depth += 1;
+            }
+            ']' | '}' => {
+                depth -= 1;
+                if depth == 1 {
+                    in_rooms = false;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    lines
+}
+
+pub fn validate(plan: &gen_plan::WorldPlan, source: Option<&str>) -> Vec<Issue> {
+    let mut validator = Validator {
+        plan,
+        lines: source.map(room_lines).unwrap_or_default(),
+        issues: Vec::new(),
+    };
+
+    if plan.rooms.is_empty() {
+        validator.report(
+            Severity::Error,
+            None,
+            String::from("The plan does not contain any room"),
+        );
+
+        return validator.issues;
+    }
+
+    let positions = validator.index_rooms();
+    let spawn = validator.check_spawn(&positions);
+    validator.check_rooms(&positions);
+
+    if let Some(spawn) = spawn {
+        validator.check_connectivity(&positions, spawn);
+    }
+
+    validator.issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed;
+
+    fn check(source: &str) -> Vec<Issue> {
+        let plan: gen_plan::WorldPlan = serde_json::from_str(source).unwrap();
+
+        validate(&plan, Some(source))
+    }
+
+    fn messages(issues: &[Issue], severity: Severity) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn accepts_a_connected_plan() {
+        let issues = check(r#"{"spawn_x":0,"spawn_y":0,"rooms":[{"x":0,"y":0},{"x":1,"y":0}]}"#);
+
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn rejects_an_empty_plan() {
+        let issues = check(r#"{"spawn_x":0,"spawn_y":0,"rooms":[]}"#);
+
+        assert!(has_errors(&issues));
+    }
+
+    #[test]
+    fn reports_duplicate_rooms_with_their_lines() {
+        let issues = check(
+            "{\"spawn_x\":0,\"spawn_y\":0,\"rooms\":[\n{\"x\":0,\"y\":0},\n{\"x\":0,\"y\":0}\n]}",
+        );
+
+        assert_eq!(
+            messages(&issues, Severity::Error),
+            vec!["Room at (0,0) was already declared on line 2 and would be overwritten"]
+        );
+        assert_eq!(issues[0].line, Some(3));
+    }
+
+    #[test]
+    fn reports_a_spawn_outside_rooms() {
+        let issues = check(r#"{"spawn_x":5,"spawn_y":5,"rooms":[{"x":0,"y":0}]}"#);
+
+        assert!(has_errors(&issues));
+    }
+
+    #[test]
+    fn warns_about_one_way_exits_missing_keys_and_unreachable_rooms() {
+        let issues = check(
+            r#"{"spawn_x":0,"spawn_y":0,"rooms":[
+                {"x":0,"y":0,"exits":[{"direction":"E"},{"direction":"N","lock":{"kind":"key","key":"red"}}]},
+                {"x":1,"y":0,"exits":[]},
+                {"x":0,"y":1,"exits":[{"direction":"S"}]}
+            ]}"#,
+        );
+
+        assert!(!has_errors(&issues));
+        assert_eq!(
+            messages(&issues, Severity::Warning).len(),
+            3,
+            "{:?}",
+            issues
+        );
+    }
+
+    #[test]
+    fn accepts_seeded_plans() {
+        for style in [
+            gen_plan::Style::Corridors,
+            gen_plan::Style::Bsp,
+            gen_plan::Style::Caves,
+        ]
+        .iter()
+        {
+            let mut seeder = seed::Seeder::from_seed(String::from("validate"));
+            let plan = gen_plan::WorldPlan::from_seeder_with_style(&mut seeder, *style, 2);
+
+            assert!(!has_errors(&validate(&plan, None)), "{:?}", style);
+        }
+    }
+}
@@ -1,43 +1,234 @@
+use crate::accounts;
 use crate::data_model;
+use crate::dump;
+use crate::events;
 use crate::gen_plan;
+use crate::i18n;
+use crate::seed;
 
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet};
+
+use std::sync::mpsc;
 use std::time;
 
 mod guid {
     pub fn random_guid() -> String {
         uuid::Uuid::new_v4().to_simple().to_string()
     }
+
+    pub fn random_token() -> String {
+        format!("{}{}", random_guid(), random_guid())
+    }
 }
 
-#[derive(Clone, Debug)]
+const DEFAULT_RESPAWN_DELAY: u64 = 300;
+const DEFAULT_PLAYER_HP: u32 = 100;
+const DEFAULT_PLAYER_DP: u32 = 10;
+const AGGRO_DELAY: time::Duration = time::Duration::from_secs(3);
+const WANDER_CHANCE: u32 = 3;
+const LEVEL_HP_BONUS: u32 = 20;
+const LEVEL_DP_BONUS: u32 = 5;
+
+fn default_player_hp() -> u32 {
+    DEFAULT_PLAYER_HP
+}
+
+fn default_player_dp() -> u32 {
+    DEFAULT_PLAYER_DP
+}
+
+fn first_level() -> u32 {
+    1
+}
+
+fn xp_for_level(level: u32) -> u32 {
+    50 * level * level.saturating_sub(1)
+}
+
+mod coords_map {
+    use super::{Coords, Room};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        rooms: &HashMap<Coords, Room>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let pairs: Vec<(&Coords, &Room)> = rooms.iter().collect();
+
+        pairs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<Coords, Room>, D::Error> {
+        let pairs: Vec<(Coords, Room)> = Vec::deserialize(deserializer)?;
+
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Item {
+    pub guid: String,
+    pub description: i18n::Text,
+    pub kind: gen_plan::ItemKind,
+}
+
+impl Item {
+    fn generate(item_plan: gen_plan::ItemPlan) -> Item {
+        let default_description = match item_plan.kind {
+            gen_plan::ItemKind::Weapon { .. } => i18n::Message::Weapon,
+            gen_plan::ItemKind::Armour { .. } => i18n::Message::Armour,
+            gen_plan::ItemKind::Potion { .. } => i18n::Message::Potion,
+            gen_plan::ItemKind::Key { .. } => i18n::Message::Key,
+        };
+        let description = item_plan
+            .description
+            .unwrap_or_else(|| default_description.into());
+
+        Item {
+            guid: guid::random_guid(),
+            description,
+            kind: item_plan.kind,
+        }
+    }
+
+    fn generate_all(item_plans: Option<Vec<gen_plan::ItemPlan>>) -> Vec<Item> {
+        item_plans
+            .unwrap_or_default()
+            .into_iter()
+            .map(Item::generate)
+            .collect()
+    }
+
+    pub fn to_data_model(&self, lang: i18n::Lang) -> data_model::Item {
+        let (r#type, value) = match self.kind {
+            gen_plan::ItemKind::Weapon { dp } => (data_model::ItemType::Weapon, dp),
+            gen_plan::ItemKind::Armour { hp } => (data_model::ItemType::Armour, hp),
+            gen_plan::ItemKind::Potion { hp } => (data_model::ItemType::Potion, hp),
+            gen_plan::ItemKind::Key { .. } => (data_model::ItemType::Key, 0),
+        };
+
+        data_model::Item {
+            guid: self.guid.clone(),
+            description: self.description.get(lang),
+            r#type,
+            value,
+        }
+    }
+}
+
+fn take_item(items: &mut Vec<Item>, item_guid: &str) -> Result<Item, data_model::WorldError> {
+    match items.iter().position(|item| item.guid == item_guid) {
+        Some(index) => Ok(items.remove(index)),
+        None => Err(data_model::WorldError::ItemNotFound(item_guid.to_string())),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum EntityType {
-    Monster(String),
+    Monster(i18n::Text),
     Player,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Entity {
     pub r#type: EntityType,
     pub max_hp: u32,
     pub hp: u32,
     pub dp: u32,
     pub location: Coords,
+    #[serde(skip, default = "time::Instant::now")]
     pub afk_since: time::Instant,
+    #[serde(skip, default = "time::Instant::now")]
+    pub arrived_at: time::Instant,
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub inventory: Vec<Item>,
+    #[serde(default)]
+    pub weapon: Option<String>,
+    #[serde(default)]
+    pub armour: Option<String>,
+    #[serde(default = "first_level")]
+    pub level: u32,
+    #[serde(default)]
+    pub xp: u32,
+    #[serde(default)]
+    pub behaviours: Vec<gen_plan::Behaviour>,
+    #[serde(default)]
+    pub lang: Option<i18n::Lang>,
+    // Room whose monsters plan spawned this monster, which stays the same when
+    // it wanders away.
+    #[serde(default)]
+    pub origin: Option<Coords>,
+    // Announcements for players without an event stream, handed out with
+    // their next look at the room.
+    #[serde(skip)]
+    pub announcements: Vec<String>,
 }
 
 impl Entity {
-    fn random_monster(location: Coords) -> Entity {
-        let hp = fastrand::u32(30..150);
+    fn equipped_bonus(&self, slot: &Option<String>) -> u32 {
+        let item = match slot {
+            Some(item_guid) => self.inventory.iter().find(|item| &item.guid == item_guid),
+            None => None,
+        };
 
-        Entity {
-            r#type: EntityType::Monster(String::from("A simple monster")),
-            max_hp: hp,
-            hp: hp,
-            dp: fastrand::u32(10..30),
-            location: location,
-            afk_since: time::Instant::now(),
+        match item.map(|item| &item.kind) {
+            Some(gen_plan::ItemKind::Weapon { dp }) => *dp,
+            Some(gen_plan::ItemKind::Armour { hp }) => *hp,
+            _ => 0,
+        }
+    }
+
+    pub fn effective_dp(&self) -> u32 {
+        self.dp + self.equipped_bonus(&self.weapon)
+    }
+
+    pub fn effective_max_hp(&self) -> u32 {
+        self.max_hp + self.equipped_bonus(&self.armour)
+    }
+
+    pub fn xp_reward(&self) -> u32 {
+        (self.max_hp + self.dp * 3) / 4 * self.level
+    }
+
+    fn gain_xp(&mut self, xp: u32) -> bool {
+        let previous_level = self.level;
+        self.xp += xp;
+
+        while self.xp >= xp_for_level(self.level + 1) {
+            self.level += 1;
+            self.max_hp += LEVEL_HP_BONUS;
+            self.dp += LEVEL_DP_BONUS;
+        }
+
+        if self.level > previous_level {
+            self.hp = self.effective_max_hp();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn to_inventory(&self, lang: i18n::Lang) -> data_model::Inventory {
+        data_model::Inventory {
+            items: self
+                .inventory
+                .iter()
+                .map(|item| item.to_data_model(lang))
+                .collect(),
+            weapon: self.weapon.clone(),
+            armour: self.armour.clone(),
+            dp: self.effective_dp(),
+            life: self.hp,
+            total_life: self.effective_max_hp(),
         }
     }
 
@@ -49,87 +240,115 @@ impl Entity {
             r#type: EntityType::Monster(
                 defined_monster_plan
                     .description
-                    .unwrap_or(String::from("A simple monster")),
+                    .unwrap_or_else(|| i18n::Message::SimpleMonster.into()),
             ),
             max_hp: defined_monster_plan.hp,
             hp: defined_monster_plan.hp,
             dp: defined_monster_plan.dp,
-            location: location,
+            origin: Some(location.clone()),
+            announcements: Vec::new(),
+            location,
             afk_since: time::Instant::now(),
+            arrived_at: time::Instant::now(),
+            account: None,
+            inventory: Item::generate_all(defined_monster_plan.loot),
+            weapon: None,
+            armour: None,
+            level: first_level(),
+            xp: 0,
+            behaviours: defined_monster_plan.behaviours.unwrap_or_default(),
+            lang: None,
         }
     }
 
-    fn generate_monsters(location: Coords, monsters_plan: gen_plan::MonstersPlan) -> Vec<Entity> {
-        match monsters_plan {
-            gen_plan::MonstersPlan::Random(length) => {
-                let mut random_monsters: Vec<Entity> = Vec::new();
-
-                for _ in 0..length {
-                    random_monsters.push(Entity::random_monster(location.clone()));
-                }
-
-                random_monsters
-            }
-            gen_plan::MonstersPlan::Defined(monster_plans) => {
-                let mut defined_monsters: Vec<Entity> = Vec::new();
-
-                for monster_plan in monster_plans.iter() {
-                    defined_monsters.push(Entity::generate_monster(
-                        location.clone(),
-                        monster_plan.clone(),
-                    ));
-                }
-
-                defined_monsters
-            }
-        }
+    fn generate_monsters(
+        location: Coords,
+        monsters_plan: gen_plan::MonstersPlan,
+        seeder: &mut seed::Seeder,
+    ) -> Vec<Entity> {
+        monsters_plan
+            .resolve(seeder)
+            .into_iter()
+            .map(|monster_plan| Entity::generate_monster(location.clone(), monster_plan))
+            .collect()
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Room {
-    pub description: String,
+    pub description: i18n::Text,
     pub guids: Vec<String>,
     pub hp_regen: Option<u32>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub monsters_plan: Option<gen_plan::MonstersPlan>,
+    #[serde(default)]
+    pub respawn_delay: Option<u64>,
+    #[serde(skip)]
+    pub cleared_since: Option<time::Instant>,
+    #[serde(default)]
+    pub exits: Option<Vec<gen_plan::ExitPlan>>,
+    #[serde(default)]
+    pub stairs: Option<gen_plan::Stairs>,
 }
 
 impl Room {
     pub fn generate_with_coords_and_entities(
         room_plan: gen_plan::RoomPlan,
+        seeder: &mut seed::Seeder,
     ) -> (Coords, Room, HashMap<String, Entity>) {
         let coords = Coords {
             x: room_plan.x,
             y: room_plan.y,
+            z: room_plan.z,
         };
 
-        let description = room_plan.description.unwrap_or(String::from("A room"));
+        let description = room_plan
+            .description
+            .unwrap_or_else(|| i18n::Message::DefaultRoom.into());
         let mut guids: Vec<String> = Vec::new();
         let mut entities: HashMap<String, Entity> = HashMap::new();
 
-        match room_plan.monsters {
-            Some(monsters_plan) => {
-                let monsters = Entity::generate_monsters(coords.clone(), monsters_plan);
+        if let Some(monsters_plan) = room_plan.monsters.clone() {
+            let monsters = Entity::generate_monsters(coords.clone(), monsters_plan, seeder);
 
-                for monster in monsters.iter() {
-                    let guid = guid::random_guid();
-                    guids.push(guid.clone());
-                    entities.insert(guid, monster.clone());
-                }
+            for monster in monsters.iter() {
+                let guid = guid::random_guid();
+                guids.push(guid.clone());
+                entities.insert(guid, monster.clone());
             }
-            None => (),
         }
 
         (
             coords,
             Room {
-                description: description,
-                guids: guids,
+                description,
+                guids,
                 hp_regen: room_plan.hp_regen,
+                items: Item::generate_all(room_plan.items),
+                monsters_plan: room_plan.monsters,
+                respawn_delay: room_plan.respawn_delay,
+                cleared_since: None,
+                exits: room_plan.exits,
+                stairs: room_plan.stairs,
             },
             entities,
         )
     }
 
+    fn allows(&self, direction: &data_model::Direction) -> bool {
+        gen_plan::allows(&self.exits, &self.stairs, direction)
+    }
+
+    fn lock(&self, direction: &data_model::Direction) -> Option<gen_plan::Lock> {
+        self.exits
+            .as_ref()?
+            .iter()
+            .find(|exit| &exit.direction == direction)
+            .and_then(|exit| exit.lock.clone())
+    }
+
     pub fn remove_guid(&mut self, guid: String) -> Result<(), data_model::WorldError> {
         let mut found = false;
 
@@ -149,9 +368,7 @@ impl Room {
 
     pub fn add_guid(&mut self, guid: String) -> Result<(), data_model::WorldError> {
         if self.guids.contains(&guid) {
-            Err(data_model::WorldError::Other(String::from(
-                "Entity is already present in this room!",
-            )))
+            Err(data_model::WorldError::Other(i18n::Message::AlreadyInRoom))
         } else {
             self.guids.push(guid);
             Ok(())
@@ -159,10 +376,12 @@ impl Room {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Coords {
     x: isize,
     y: isize,
+    #[serde(default)]
+    z: isize,
 }
 
 impl Coords {
@@ -170,6 +389,7 @@ impl Coords {
         Some(Coords {
             x: self.x,
             y: self.y.checked_add(1)?,
+            z: self.z,
         })
     }
 
@@ -177,6 +397,7 @@ impl Coords {
         Some(Coords {
             x: self.x,
             y: self.y.checked_sub(1)?,
+            z: self.z,
         })
     }
 
@@ -184,6 +405,7 @@ impl Coords {
         Some(Coords {
             x: self.x.checked_add(1)?,
             y: self.y,
+            z: self.z,
         })
     }
 
@@ -191,16 +413,62 @@ impl Coords {
         Some(Coords {
             x: self.x.checked_sub(1)?,
             y: self.y,
+            z: self.z,
+        })
+    }
+
+    pub fn up(&self) -> Option<Coords> {
+        Some(Coords {
+            x: self.x,
+            y: self.y,
+            z: self.z.checked_add(1)?,
+        })
+    }
+
+    pub fn down(&self) -> Option<Coords> {
+        Some(Coords {
+            x: self.x,
+            y: self.y,
+            z: self.z.checked_sub(1)?,
         })
     }
+
+    pub fn towards(&self, direction: &data_model::Direction) -> Option<Coords> {
+        match direction {
+            data_model::Direction::N => self.north(),
+            data_model::Direction::S => self.south(),
+            data_model::Direction::E => self.east(),
+            data_model::Direction::W => self.west(),
+            data_model::Direction::U => self.up(),
+            data_model::Direction::D => self.down(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct World {
+    #[serde(with = "coords_map")]
     pub rooms: HashMap<Coords, Room>,
     pub entities: HashMap<String, Entity>,
     pub spawn: Coords,
     pub afk_threshold: time::Duration,
+    pub respawn_delay: time::Duration,
+    #[serde(default = "default_player_hp")]
+    pub player_hp: u32,
+    #[serde(default = "default_player_dp")]
+    pub player_dp: u32,
+    #[serde(skip)]
+    pub sessions: HashMap<String, String>,
+    // Sessions of removed players, kept for a while to tell them they
+    // disappeared rather than that their token is invalid.
+    #[serde(skip)]
+    pub ended_sessions: HashMap<String, (String, time::Instant)>,
+    #[serde(skip)]
+    pub subscribers: events::Subscribers,
+    #[serde(skip)]
+    pub accounts: accounts::Accounts,
+    #[serde(skip, default = "seed::Seeder::random")]
+    pub seeder: seed::Seeder,
 }
 
 impl World {
@@ -208,17 +476,25 @@ impl World {
         World {
             rooms: HashMap::new(),
             entities: HashMap::new(),
-            spawn: Coords { x: 0, y: 0 },
+            spawn: Coords { x: 0, y: 0, z: 0 },
             afk_threshold: time::Duration::from_secs(60),
+            respawn_delay: time::Duration::from_secs(DEFAULT_RESPAWN_DELAY),
+            player_hp: DEFAULT_PLAYER_HP,
+            player_dp: DEFAULT_PLAYER_DP,
+            sessions: HashMap::new(),
+            ended_sessions: HashMap::new(),
+            subscribers: events::Subscribers::default(),
+            accounts: accounts::Accounts::default(),
+            seeder: seed::Seeder::random(),
         }
     }
 
-    pub fn generate(world_plan: &gen_plan::WorldPlan) -> World {
+    pub fn generate(world_plan: &gen_plan::WorldPlan, mut seeder: seed::Seeder) -> World {
         let mut world = World::new();
 
         for room_plan in world_plan.rooms.iter() {
             let (coords, room, entities) =
-                Room::generate_with_coords_and_entities(room_plan.clone());
+                Room::generate_with_coords_and_entities(room_plan.clone(), &mut seeder);
 
             world.rooms.insert(coords, room);
             world.entities.extend(entities);
@@ -226,6 +502,13 @@ impl World {
 
         world.spawn.x = world_plan.spawn_x;
         world.spawn.y = world_plan.spawn_y;
+        world.spawn.z = world_plan.spawn_z;
+
+        if let Some(respawn_delay) = world_plan.respawn_delay {
+            world.respawn_delay = time::Duration::from_secs(respawn_delay);
+        }
+
+        world.seeder = seeder;
 
         world
     }
@@ -245,59 +528,446 @@ impl World {
     }
 
     pub fn disconnect_afk_players(&mut self) -> Result<(), data_model::WorldError> {
-        let mut entities_to_remove: Vec<(Coords, String)> = Vec::new();
+        let mut entities_to_remove: Vec<String> = Vec::new();
+        let threshold = self.afk_threshold;
+
+        self.ended_sessions
+            .retain(|_, (_, ended)| ended.elapsed() <= threshold);
 
         for entity in self.entities.iter_mut() {
             match entity.1.r#type {
                 EntityType::Monster(_) => (),
                 EntityType::Player => {
                     let guid = entity.0.clone();
-                    let location = entity.1.location.clone();
                     if time::Instant::now() - entity.1.afk_since > self.afk_threshold {
-                        entities_to_remove.push((location, guid));
+                        entities_to_remove.push(guid);
                     }
                 }
             }
         }
 
-        for (location, guid) in entities_to_remove.iter() {
-            self.get_room(location.clone())?.remove_guid(guid.clone())?;
-            self.entities.remove(guid);
+        for guid in entities_to_remove.iter() {
+            self.disconnect(guid.clone())?;
         }
 
         Ok(())
     }
 
-    pub fn connect(&mut self) -> Result<data_model::Status, data_model::WorldError> {
-        let coords = self.spawn.clone();
-        let room = self.get_room(coords.clone())?;
-        let guid = guid::random_guid();
-        let max_hp = 100;
+    pub fn disconnect(&mut self, guid: String) -> Result<(), data_model::WorldError> {
+        let location = self.get_entity(guid.clone())?.location.clone();
+
+        self.remove_entity(guid.clone())?;
+        self.notify_room(location, data_model::RoomEvent::Disconnected { guid });
+
+        Ok(())
+    }
+
+    pub fn kick(&mut self, guid: String) -> Result<(), data_model::WorldError> {
+        match self.get_entity(guid.clone())?.r#type {
+            EntityType::Player => self.disconnect(guid),
+            EntityType::Monster(_) => Err(data_model::WorldError::EntityNotFound(guid)),
+        }
+    }
+
+    pub fn players(&self) -> Vec<data_model::PlayerSummary> {
+        self.entities
+            .iter()
+            .filter(|(_, entity)| matches!(entity.r#type, EntityType::Player))
+            .map(|(guid, entity)| data_model::PlayerSummary {
+                guid: guid.clone(),
+                username: entity.account.clone(),
+                level: entity.level,
+                life: entity.hp,
+                total_life: entity.effective_max_hp(),
+                x: entity.location.x,
+                y: entity.location.y,
+                z: entity.location.z,
+                idle_seconds: entity.afk_since.elapsed().as_secs(),
+            })
+            .collect()
+    }
+
+    pub fn broadcast(&mut self, message: String) {
+        let event = data_model::RoomEvent::Announced {
+            message: message.clone(),
+        };
+
+        for (guid, entity) in self.entities.iter_mut() {
+            if matches!(entity.r#type, EntityType::Player) && !self.subscribers.send(guid, &event) {
+                entity.announcements.push(message.clone());
+            }
+        }
+    }
+
+    fn remove_entity(&mut self, guid: String) -> Result<(), data_model::WorldError> {
+        let entity = self.get_entity(guid.clone())?.clone();
+
+        if let Some(username) = entity.account.clone() {
+            let player = if entity.hp > 0 {
+                Some(entity.clone())
+            } else {
+                None
+            };
+            self.accounts.store_players(vec![(username, player)]);
+        }
+
+        self.get_room(entity.location)?.remove_guid(guid.clone())?;
+        self.entities.remove(&guid);
+        self.subscribers.unsubscribe(&guid);
+
+        let now = time::Instant::now();
+        let ended_sessions = &mut self.ended_sessions;
+        self.sessions.retain(|token, session_guid| {
+            if session_guid == &guid {
+                ended_sessions.insert(token.clone(), (guid.clone(), now));
+                false
+            } else {
+                true
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn authorize(&self, token: &str, guid: &str) -> Result<(), data_model::WorldError> {
+        let session_guid = match self.sessions.get(token) {
+            Some(session_guid) => Some(session_guid),
+            None => self.ended_sessions.get(token).map(|(guid, _)| guid),
+        };
+
+        match session_guid {
+            Some(session_guid) if session_guid != guid => Err(data_model::WorldError::Unauthorized),
+            Some(_) if self.entities.contains_key(guid) => Ok(()),
+            Some(_) => Err(data_model::WorldError::Disappeared),
+            None => Err(data_model::WorldError::Unauthorized),
+        }
+    }
+
+    // A delay of zero disables respawning for the room.
+    pub fn respawn_monsters(&mut self) {
+        let mut rooms_to_fill: Vec<(Coords, gen_plan::MonstersPlan)> = Vec::new();
+        // Monsters from older snapshots have no origin, they count for the
+        // room they stand in.
+        let occupied: HashSet<&Coords> = self
+            .entities
+            .values()
+            .filter(|entity| matches!(entity.r#type, EntityType::Monster(_)))
+            .map(|entity| entity.origin.as_ref().unwrap_or(&entity.location))
+            .collect();
+
+        for (coords, room) in self.rooms.iter_mut() {
+            let monsters_plan = match &room.monsters_plan {
+                Some(monsters_plan) => monsters_plan.clone(),
+                None => continue,
+            };
+
+            if occupied.contains(coords) {
+                room.cleared_since = None;
+                continue;
+            }
+
+            let respawn_delay = match room.respawn_delay {
+                Some(respawn_delay) => time::Duration::from_secs(respawn_delay),
+                None => self.respawn_delay,
+            };
+
+            match room.cleared_since {
+                None => room.cleared_since = Some(time::Instant::now()),
+                Some(cleared_since) => {
+                    if respawn_delay.as_secs() > 0 && cleared_since.elapsed() >= respawn_delay {
+                        room.cleared_since = None;
+                        rooms_to_fill.push((coords.clone(), monsters_plan));
+                    }
+                }
+            }
+        }
+
+        for (coords, monsters_plan) in rooms_to_fill.into_iter() {
+            let monsters =
+                Entity::generate_monsters(coords.clone(), monsters_plan, &mut self.seeder);
+
+            for monster in monsters.into_iter() {
+                let guid = guid::random_guid();
+
+                self.notify_room(
+                    coords.clone(),
+                    data_model::RoomEvent::Spawned { guid: guid.clone() },
+                );
+                if let Some(room) = self.rooms.get_mut(&coords) {
+                    room.guids.push(guid.clone());
+                }
+                self.entities.insert(guid, monster);
+            }
+        }
+    }
+
+    pub fn tick_monsters(&mut self) {
+        let monster_guids: Vec<String> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| !entity.behaviours.is_empty())
+            .map(|(guid, _)| guid.clone())
+            .collect();
+
+        for guid in monster_guids.into_iter() {
+            // Earlier monsters may have died fighting during this tick.
+            let monster = match self.entities.get(&guid) {
+                Some(monster) => monster.clone(),
+                None => continue,
+            };
+            let players = self.players_in_room(&monster.location);
+
+            if monster.behaviours.contains(&gen_plan::Behaviour::Flee)
+                && monster.hp * 4 <= monster.max_hp
+                && !players.is_empty()
+            {
+                self.wander(guid);
+                continue;
+            }
+
+            if monster
+                .behaviours
+                .contains(&gen_plan::Behaviour::Aggressive)
+            {
+                let lingering: Vec<String> = players
+                    .into_iter()
+                    .filter(|player_guid| match self.entities.get(player_guid) {
+                        Some(player) => player.arrived_at.elapsed() >= AGGRO_DELAY,
+                        None => false,
+                    })
+                    .collect();
+
+                if !lingering.is_empty() {
+                    let target = lingering[fastrand::usize(..lingering.len())].clone();
+                    self.attack(guid, target).ok();
+                    continue;
+                }
+            }
+
+            if monster.behaviours.contains(&gen_plan::Behaviour::Wander)
+                && fastrand::u32(0..WANDER_CHANCE) == 0
+            {
+                self.wander(guid);
+            }
+        }
+    }
+
+    fn players_in_room(&self, location: &Coords) -> Vec<String> {
+        match self.rooms.get(location) {
+            Some(room) => room
+                .guids
+                .iter()
+                .filter(|guid| {
+                    matches!(
+                        self.entities.get(*guid).map(|entity| &entity.r#type),
+                        Some(EntityType::Player)
+                    )
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn live_tiles(&self, level: Option<isize>) -> Vec<dump::LiveTile> {
+        let level = level.unwrap_or(self.spawn.z);
+        let mut tiles = Vec::new();
+
+        for (coords, room) in self.rooms.iter().filter(|(coords, _)| coords.z == level) {
+            let mut players = 0;
+            let mut monsters = 0;
+
+            for guid in room.guids.iter() {
+                match self.entities.get(guid).map(|entity| &entity.r#type) {
+                    Some(EntityType::Player) => players += 1,
+                    Some(EntityType::Monster(_)) => monsters += 1,
+                    None => (),
+                }
+            }
+
+            let populated = match &room.monsters_plan {
+                Some(gen_plan::MonstersPlan::Random(length)) => *length > 0,
+                Some(gen_plan::MonstersPlan::Defined(monster_plans)) => !monster_plans.is_empty(),
+                None => false,
+            };
+
+            tiles.push(dump::LiveTile {
+                x: coords.x,
+                y: coords.y,
+                players,
+                monsters,
+                cleared: populated && monsters == 0,
+                stairs: room.stairs.is_some(),
+                spawn: coords == &self.spawn,
+            });
+        }
+
+        tiles
+    }
+
+    fn wander(&mut self, guid: String) {
+        let location = match self.entities.get(&guid) {
+            Some(entity) => entity.location.clone(),
+            None => return,
+        };
+        let directions = self.get_directions_for_coordinates(location);
+
+        if !directions.is_empty() {
+            let direction = directions[fastrand::usize(..directions.len())].clone();
+            self.move_entity(guid, direction).ok();
+        }
+    }
+
+    pub fn subscribe(
+        &mut self,
+        guid: String,
+    ) -> Result<mpsc::Receiver<data_model::RoomEvent>, data_model::WorldError> {
+        self.get_entity(guid.clone())?;
+
+        Ok(self.subscribers.subscribe(guid))
+    }
+
+    fn notify_room(&mut self, location: Coords, event: data_model::RoomEvent) {
+        if let Some(room) = self.rooms.get(&location) {
+            self.subscribers.notify(&room.guids, &event);
+        }
+    }
+
+    pub fn connect(
+        &mut self,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Status, data_model::WorldError> {
+        let player = self.new_player(None);
+
+        self.spawn_player(player, lang)
+    }
+
+    pub fn register(
+        &mut self,
+        username: String,
+        password_hash: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Status, data_model::WorldError> {
+        self.accounts.register(username.clone(), password_hash)?;
+
+        self.login(username, lang)
+    }
+
+    pub fn login(
+        &mut self,
+        username: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Status, data_model::WorldError> {
+        let connected_guid = self
+            .entities
+            .iter()
+            .find(|(_, entity)| entity.account.as_ref() == Some(&username))
+            .map(|(guid, _)| guid.clone());
+
+        if let Some(guid) = connected_guid {
+            self.sessions.retain(|_, session_guid| session_guid != &guid);
+            self.player_acted(guid.clone())?;
+
+            return self.new_session(guid, lang);
+        }
+
+        let player = match self.accounts.saved_player(&username) {
+            Some(mut player) => {
+                player.account = Some(username);
+                player
+            }
+            None => self.new_player(Some(username)),
+        };
+
+        self.spawn_player(player, lang)
+    }
+
+    pub fn save_accounts(&mut self) {
+        let players: Vec<(String, Option<Entity>)> = self
+            .entities
+            .values()
+            .filter_map(|entity| {
+                entity
+                    .account
+                    .clone()
+                    .map(|username| (username, Some(entity.clone())))
+            })
+            .collect();
 
-        let player = Entity {
-            dp: 10,
+        self.accounts.store_players(players);
+    }
+
+    fn new_player(&self, account: Option<String>) -> Entity {
+        let max_hp = self.player_hp;
+
+        Entity {
+            dp: self.player_dp,
             hp: max_hp,
-            max_hp: max_hp,
-            location: coords.clone(),
+            max_hp,
+            location: self.spawn.clone(),
             r#type: EntityType::Player,
             afk_since: time::Instant::now(),
-        };
+            arrived_at: time::Instant::now(),
+            account,
+            inventory: Vec::new(),
+            weapon: None,
+            armour: None,
+            level: first_level(),
+            xp: 0,
+            behaviours: Vec::new(),
+            lang: None,
+            origin: None,
+            announcements: Vec::new(),
+        }
+    }
 
-        room.add_guid(guid.clone())?;
+    fn spawn_player(
+        &mut self,
+        mut player: Entity,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Status, data_model::WorldError> {
+        if !self.rooms.contains_key(&player.location) {
+            player.location = self.spawn.clone();
+        }
+        player.afk_since = time::Instant::now();
+        player.arrived_at = time::Instant::now();
+
+        let coords = player.location.clone();
+        self.get_room(coords.clone())?;
+        let guid = guid::random_guid();
 
-        let description = room.description.clone();
-        let guids = room.guids.clone();
+        self.notify_room(
+            coords.clone(),
+            data_model::RoomEvent::Connected { guid: guid.clone() },
+        );
 
+        self.get_room(coords)?.add_guid(guid.clone())?;
         self.entities.insert(guid.clone(), player);
 
+        self.new_session(guid, lang)
+    }
+
+    fn new_session(
+        &mut self,
+        guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Status, data_model::WorldError> {
+        let token = guid::random_token();
+        let entity = self.get_entity(guid.clone())?;
+        let total_life = entity.effective_max_hp();
+        let level = entity.level;
+        let xp = entity.xp;
+        let room = self.look(guid.clone(), lang)?;
+
+        self.sessions.insert(token.clone(), guid.clone());
+
         Ok(data_model::Status {
-            guid: guid.clone(),
-            total_life: max_hp,
-            room: data_model::Room {
-                description: description,
-                entities: guids,
-                paths: self.get_directions_for_coordinates(coords.clone()),
-            },
+            guid,
+            token,
+            total_life,
+            level,
+            xp,
+            room,
         })
     }
 
@@ -307,35 +977,78 @@ impl World {
         Ok(())
     }
 
-    pub fn look(&mut self, guid: String) -> Result<data_model::Room, data_model::WorldError> {
-        let coords = self.get_entity(guid)?.location.clone();
+    // The language chosen when connecting, which takes precedence over the
+    // Accept-Language header of later requests.
+    pub fn set_lang(
+        &mut self,
+        guid: String,
+        lang: i18n::Lang,
+    ) -> Result<(), data_model::WorldError> {
+        self.get_entity(guid)?.lang = Some(lang);
+
+        Ok(())
+    }
+
+    pub fn session_lang(&self, token: &str) -> Option<i18n::Lang> {
+        let guid = self.sessions.get(token)?;
+
+        self.entities.get(guid)?.lang
+    }
+
+    pub fn look(
+        &mut self,
+        guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Room, data_model::WorldError> {
+        let coords = self.get_entity(guid.clone())?.location.clone();
+        let announcements = match self.entities.get_mut(&guid) {
+            Some(entity) => std::mem::take(&mut entity.announcements),
+            None => Vec::new(),
+        };
         let room = self.get_room(coords.clone())?;
 
         Ok(data_model::Room {
-            description: room.description.clone(),
+            description: room.description.get(lang),
             entities: room.guids.clone(),
+            items: room
+                .items
+                .iter()
+                .map(|item| item.to_data_model(lang))
+                .collect(),
             paths: self.get_directions_for_coordinates(coords),
+            announcements,
         })
     }
 
+    // The room an entity stands in, without consuming its announcements like
+    // `look` does.
+    pub fn current_room(&self, guid: &str) -> Option<&Room> {
+        let location = &self.entities.get(guid)?.location;
+
+        self.rooms.get(location)
+    }
+
     pub fn look_entity(
         &mut self,
         guid: String,
         guid_dest: String,
+        lang: i18n::Lang,
     ) -> Result<data_model::Entity, data_model::WorldError> {
         let coords = self.get_entity(guid)?.location.clone();
-        let entity = self.get_entity(guid_dest.clone())?;
+        let entity = self.get_entity(guid_dest)?;
 
         if coords != entity.location {
-            return Err(data_model::WorldError::DiffRoom);
+            Err(data_model::WorldError::DiffRoom)
         } else {
             Ok(data_model::Entity {
                 description: match entity.r#type.clone() {
-                    EntityType::Player => String::from("Another player"),
-                    EntityType::Monster(description) => description,
+                    EntityType::Player => i18n::Message::AnotherPlayer.translate(lang),
+                    EntityType::Monster(description) => description.get(lang),
                 },
                 life: entity.hp,
-                total_life: entity.max_hp,
+                total_life: entity.effective_max_hp(),
+                level: entity.level,
+                xp: entity.xp,
                 r#type: match entity.r#type {
                     EntityType::Player => data_model::EntityType::Player,
                     EntityType::Monster(_) => data_model::EntityType::Monster,
@@ -348,75 +1061,291 @@ impl World {
         &mut self,
         guid: String,
         direction: data_model::Direction,
+        lang: i18n::Lang,
     ) -> Result<data_model::Room, data_model::WorldError> {
         self.player_acted(guid.clone())?;
 
-        let coords = self.get_entity(guid.clone())?.location.clone();
-        let mut new_coords = coords.clone();
-        match direction {
-            data_model::Direction::N => new_coords.y += 1,
-            data_model::Direction::S => new_coords.y -= 1,
-            data_model::Direction::E => new_coords.x += 1,
-            data_model::Direction::W => new_coords.x -= 1,
-        }
-        self.get_room(new_coords.clone())?;
-        let prev_room = self.get_room(coords.clone())?;
-        prev_room.remove_guid(guid.clone())?;
+        let new_coords = self.move_entity(guid.clone(), direction)?;
         let next_room = self.get_room(new_coords.clone())?;
-        next_room.add_guid(guid.clone())?;
         let guids = next_room.guids.clone();
-        let cloned_guids = guids.clone();
-        let description = next_room.description.clone();
-        let hp_regen = next_room.hp_regen.clone();
-        let hp = self.get_entity(guid.clone())?.hp.clone();
-        let max_hp = self.get_entity(guid.clone())?.max_hp.clone();
-        let new_hp = match hp_regen {
-            Some(value) => std::cmp::min(hp + value, max_hp),
-            None => hp.clone(),
+        let description = next_room.description.get(lang);
+        let items = next_room
+            .items
+            .iter()
+            .map(|item| item.to_data_model(lang))
+            .collect();
+        let hp_regen = next_room.hp_regen;
+        let entity = self.get_entity(guid)?;
+        let max_hp = entity.effective_max_hp();
+        entity.hp = match hp_regen {
+            Some(value) => std::cmp::min(entity.hp + value, max_hp),
+            None => entity.hp,
         };
-        self.get_entity(guid.clone())?.hp = new_hp;
-        self.get_entity(guid.clone())?.location = new_coords.clone();
+        let announcements = std::mem::take(&mut entity.announcements);
 
         Ok(data_model::Room {
-            description: description,
-            entities: cloned_guids,
+            description,
+            entities: guids,
+            items,
             paths: self.get_directions_for_coordinates(new_coords),
+            announcements,
         })
     }
 
-    fn get_directions_for_coordinates(&mut self, coords: Coords) -> Vec<data_model::Direction> {
-        let mut directions = Vec::new();
-
-        match coords.north() {
-            Some(new_coords) => match self.get_room(new_coords) {
-                Ok(_) => directions.push(data_model::Direction::N),
-                Err(_) => (),
+    fn move_entity(
+        &mut self,
+        guid: String,
+        direction: data_model::Direction,
+    ) -> Result<Coords, data_model::WorldError> {
+        let coords = self.get_entity(guid.clone())?.location.clone();
+        let new_coords = match self.passage(&coords, &direction) {
+            Some(new_coords) => new_coords,
+            None => return Err(data_model::WorldError::Wall),
+        };
+        self.check_locks(&guid, &coords, &new_coords, &direction)?;
+        let prev_room = self.get_room(coords.clone())?;
+        prev_room.remove_guid(guid.clone())?;
+        self.notify_room(
+            coords,
+            data_model::RoomEvent::Left {
+                guid: guid.clone(),
+                direction,
             },
-            None => (),
+        );
+        self.notify_room(
+            new_coords.clone(),
+            data_model::RoomEvent::Entered { guid: guid.clone() },
+        );
+        self.get_room(new_coords.clone())?.add_guid(guid.clone())?;
+
+        let entity = self.get_entity(guid)?;
+        entity.location = new_coords.clone();
+        entity.arrived_at = time::Instant::now();
+
+        Ok(new_coords)
+    }
+
+    fn award_xp(&mut self, guid: String, xp: u32) -> Result<(), data_model::WorldError> {
+        let entity = self.get_entity(guid.clone())?;
+
+        if entity.gain_xp(xp) {
+            let location = entity.location.clone();
+            let level = entity.level;
+
+            self.notify_room(location, data_model::RoomEvent::LevelUp { guid, level });
         }
 
-        match coords.south() {
-            Some(new_coords) => match self.get_room(new_coords) {
-                Ok(_) => directions.push(data_model::Direction::S),
-                Err(_) => (),
-            },
-            None => (),
+        Ok(())
+    }
+
+    fn drop_inventory(&mut self, guid: String) -> Result<(), data_model::WorldError> {
+        let entity = self.get_entity(guid)?;
+        let location = entity.location.clone();
+        let items: Vec<Item> = entity.inventory.drain(..).collect();
+        entity.weapon = None;
+        entity.armour = None;
+
+        self.get_room(location)?.items.extend(items);
+
+        Ok(())
+    }
+
+    pub fn inventory(
+        &mut self,
+        guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Inventory, data_model::WorldError> {
+        Ok(self.get_entity(guid)?.to_inventory(lang))
+    }
+
+    pub fn pick_up(
+        &mut self,
+        guid: String,
+        item_guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Inventory, data_model::WorldError> {
+        self.player_acted(guid.clone())?;
+
+        let location = self.get_entity(guid.clone())?.location.clone();
+        let item = take_item(&mut self.get_room(location)?.items, &item_guid)?;
+        let entity = self.get_entity(guid)?;
+        entity.inventory.push(item);
+
+        Ok(entity.to_inventory(lang))
+    }
+
+    pub fn drop_item(
+        &mut self,
+        guid: String,
+        item_guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Inventory, data_model::WorldError> {
+        self.player_acted(guid.clone())?;
+
+        let entity = self.get_entity(guid.clone())?;
+        let item = take_item(&mut entity.inventory, &item_guid)?;
+        if entity.weapon.as_ref() == Some(&item_guid) {
+            entity.weapon = None;
+        }
+        if entity.armour.as_ref() == Some(&item_guid) {
+            entity.armour = None;
         }
+        entity.hp = std::cmp::min(entity.hp, entity.effective_max_hp());
+        let location = entity.location.clone();
 
-        match coords.east() {
-            Some(new_coords) => match self.get_room(new_coords) {
-                Ok(_) => directions.push(data_model::Direction::E),
-                Err(_) => (),
-            },
-            None => (),
+        self.get_room(location)?.items.push(item);
+
+        Ok(self.get_entity(guid)?.to_inventory(lang))
+    }
+
+    pub fn equip(
+        &mut self,
+        guid: String,
+        item_guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Inventory, data_model::WorldError> {
+        self.player_acted(guid.clone())?;
+
+        let entity = self.get_entity(guid)?;
+        let kind = match entity.inventory.iter().find(|item| item.guid == item_guid) {
+            Some(item) => item.kind.clone(),
+            None => return Err(data_model::WorldError::ItemNotFound(item_guid)),
+        };
+
+        match kind {
+            gen_plan::ItemKind::Weapon { .. } => entity.weapon = Some(item_guid),
+            gen_plan::ItemKind::Armour { .. } => {
+                entity.armour = Some(item_guid);
+                entity.hp = std::cmp::min(entity.hp, entity.effective_max_hp());
+            }
+            gen_plan::ItemKind::Potion { .. } => {
+                return Err(data_model::WorldError::Other(
+                    i18n::Message::CannotEquipPotion,
+                ))
+            }
+            gen_plan::ItemKind::Key { .. } => {
+                return Err(data_model::WorldError::Other(i18n::Message::CannotEquipKey))
+            }
         }
 
-        match coords.west() {
-            Some(new_coords) => match self.get_room(new_coords) {
-                Ok(_) => directions.push(data_model::Direction::W),
-                Err(_) => (),
-            },
-            None => (),
+        Ok(entity.to_inventory(lang))
+    }
+
+    pub fn use_item(
+        &mut self,
+        guid: String,
+        item_guid: String,
+        lang: i18n::Lang,
+    ) -> Result<data_model::Inventory, data_model::WorldError> {
+        self.player_acted(guid.clone())?;
+
+        let entity = self.get_entity(guid)?;
+        let heal = match entity.inventory.iter().find(|item| item.guid == item_guid) {
+            Some(Item {
+                kind: gen_plan::ItemKind::Potion { hp },
+                ..
+            }) => *hp,
+            Some(_) => return Err(data_model::WorldError::Other(i18n::Message::CannotUseItem)),
+            None => return Err(data_model::WorldError::ItemNotFound(item_guid)),
+        };
+
+        take_item(&mut entity.inventory, &item_guid)?;
+        entity.hp = std::cmp::min(entity.hp + heal, entity.effective_max_hp());
+
+        Ok(entity.to_inventory(lang))
+    }
+
+    fn passage(&self, coords: &Coords, direction: &data_model::Direction) -> Option<Coords> {
+        let new_coords = coords.towards(direction)?;
+        let room = self.rooms.get(coords)?;
+        let next_room = self.rooms.get(&new_coords)?;
+
+        if room.allows(direction) && next_room.allows(&direction.opposite()) {
+            Some(new_coords)
+        } else {
+            None
+        }
+    }
+
+    fn check_locks(
+        &self,
+        guid: &str,
+        coords: &Coords,
+        new_coords: &Coords,
+        direction: &data_model::Direction,
+    ) -> Result<(), data_model::WorldError> {
+        let entity = match self.entities.get(guid) {
+            Some(entity) => entity,
+            None => return Err(data_model::WorldError::EntityNotFound(guid.to_string())),
+        };
+        let sides = [
+            (coords, direction.clone()),
+            (new_coords, direction.opposite()),
+        ];
+
+        for (side, side_direction) in sides.iter() {
+            let room = match self.rooms.get(side) {
+                Some(room) => room,
+                None => return Err(data_model::WorldError::Wall),
+            };
+
+            match room.lock(side_direction) {
+                Some(gen_plan::Lock::Key { key }) => {
+                    let has_key = entity.inventory.iter().any(|item| match &item.kind {
+                        gen_plan::ItemKind::Key { key: item_key } => item_key == &key,
+                        _ => false,
+                    });
+
+                    if !has_key {
+                        return Err(data_model::WorldError::Locked(i18n::Message::LockedByKey(
+                            key,
+                        )));
+                    }
+                }
+                Some(gen_plan::Lock::Cleared) => {
+                    let guarded = room.guids.iter().any(|room_guid| {
+                        matches!(
+                            self.entities.get(room_guid).map(|entity| &entity.r#type),
+                            Some(EntityType::Monster(_))
+                        )
+                    });
+
+                    if guarded {
+                        return Err(data_model::WorldError::Locked(
+                            i18n::Message::LockedUntilCleared,
+                        ));
+                    }
+                }
+                Some(gen_plan::Lock::Level { level }) => {
+                    if entity.level < level {
+                        return Err(data_model::WorldError::Locked(
+                            i18n::Message::LockedUntilLevel(level),
+                        ));
+                    }
+                }
+                None => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_directions_for_coordinates(&mut self, coords: Coords) -> Vec<data_model::Direction> {
+        let mut directions = Vec::new();
+
+        for direction in [
+            data_model::Direction::N,
+            data_model::Direction::S,
+            data_model::Direction::E,
+            data_model::Direction::W,
+            data_model::Direction::U,
+            data_model::Direction::D,
+        ]
+        .iter()
+        {
+            if self.passage(&coords, direction).is_some() {
+                directions.push(direction.clone());
+            }
         }
 
         directions
@@ -435,45 +1364,409 @@ impl World {
         if attacker.location != defender.location {
             Err(data_model::WorldError::DiffRoom)
         } else {
-            attacker.hp = attacker.hp.saturating_sub(defender.dp);
-            defender.hp = defender.hp.saturating_sub(attacker.dp);
+            attacker.hp = attacker.hp.saturating_sub(defender.effective_dp());
+            defender.hp = defender.hp.saturating_sub(attacker.effective_dp());
 
             let dead = attacker.hp == 0;
 
+            let fight = data_model::Fight {
+                attacker: data_model::Fighter {
+                    guid: attacker_guid.clone(),
+                    dp: attacker.effective_dp(),
+                    hp: attacker.hp,
+                },
+                defender: data_model::Fighter {
+                    guid: defender_guid.clone(),
+                    dp: defender.effective_dp(),
+                    hp: defender.hp,
+                },
+            };
+
+            self.notify_room(
+                attacker.location.clone(),
+                data_model::RoomEvent::Attacked {
+                    fight: fight.clone(),
+                },
+            );
+
             self.get_entity(attacker_guid.clone())?.hp = attacker.hp;
 
             if dead {
-                self.get_room(attacker.location)?
-                    .remove_guid(attacker_guid.clone())?;
-                self.entities.remove(&attacker_guid);
+                self.notify_room(
+                    attacker.location.clone(),
+                    data_model::RoomEvent::Died {
+                        guid: attacker_guid.clone(),
+                    },
+                );
+                self.drop_inventory(attacker_guid.clone())?;
+                self.remove_entity(attacker_guid.clone())?;
             }
 
             self.get_entity(defender_guid.clone())?.hp = defender.hp;
 
             if defender.hp == 0 {
-                self.get_room(defender.location)?
-                    .remove_guid(defender_guid.clone())?;
-                self.entities.remove(&defender_guid);
+                self.notify_room(
+                    defender.location.clone(),
+                    data_model::RoomEvent::Died {
+                        guid: defender_guid.clone(),
+                    },
+                );
+                self.drop_inventory(defender_guid.clone())?;
+                self.remove_entity(defender_guid)?;
+
+                if !dead {
+                    if let EntityType::Player = attacker.r#type {
+                        self.award_xp(attacker_guid, defender.xp_reward())?;
+                    }
+                }
             } else {
                 self.get_entity(defender_guid.clone())?.hp = defender.hp;
+
+                if dead {
+                    if let EntityType::Player = defender.r#type {
+                        self.award_xp(defender_guid, attacker.xp_reward())?;
+                    }
+                }
             }
 
             if dead {
                 Err(data_model::WorldError::Disappeared)
             } else {
-                Ok(data_model::Fight {
-                    attacker: data_model::Fighter {
-                        guid: attacker_guid,
-                        dp: attacker.dp,
-                        hp: attacker.hp,
-                    },
-                    defender: data_model::Fighter {
-                        guid: defender_guid,
-                        dp: defender.dp,
-                        hp: defender.hp,
-                    },
-                })
+                Ok(fight)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_from(source: &str) -> World {
+        let plan: gen_plan::WorldPlan = serde_json::from_str(source).unwrap();
+
+        World::generate(&plan, seed::Seeder::from_seed(String::from("world")))
+    }
+
+    fn world() -> World {
+        world_from(r#"{"spawn_x":0,"spawn_y":0,"rooms":[{"x":0,"y":0}]}"#)
+    }
+
+    fn assert_closed(receiver: mpsc::Receiver<data_model::RoomEvent>) {
+        while receiver.try_recv().is_ok() {}
+
+        assert_eq!(
+            receiver.try_recv().unwrap_err(),
+            mpsc::TryRecvError::Disconnected
+        );
+    }
+
+    #[test]
+    fn kicked_players_lose_their_event_stream() {
+        let mut world = world();
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let receiver = world.subscribe(guid.clone()).unwrap();
+
+        world.kick(guid).unwrap();
+
+        assert_closed(receiver);
+    }
+
+    #[test]
+    fn afk_players_lose_their_event_stream() {
+        let mut world = world();
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let receiver = world.subscribe(guid).unwrap();
+
+        world.afk_threshold = time::Duration::from_secs(0);
+        world.disconnect_afk_players().unwrap();
+
+        assert_closed(receiver);
+    }
+
+    #[test]
+    fn announcements_wait_for_players_without_event_stream() {
+        let mut world = world();
+        let streaming = world.connect(i18n::Lang::En).unwrap().guid;
+        let polling = world.connect(i18n::Lang::En).unwrap().guid;
+        let receiver = world.subscribe(streaming.clone()).unwrap();
+
+        world.broadcast(String::from("Server restarting"));
+
+        assert!(receiver.try_iter().any(|event| matches!(
+            event,
+            data_model::RoomEvent::Announced { message } if message == "Server restarting"
+        )));
+        assert!(world
+            .look(streaming, i18n::Lang::En)
+            .unwrap()
+            .announcements
+            .is_empty());
+        assert_eq!(
+            world
+                .look(polling.clone(), i18n::Lang::En)
+                .unwrap()
+                .announcements,
+            vec![String::from("Server restarting")]
+        );
+        assert!(world
+            .look(polling, i18n::Lang::En)
+            .unwrap()
+            .announcements
+            .is_empty());
+    }
+
+    #[test]
+    fn snapshots_keep_players_but_not_sessions() {
+        let mut world = world();
+        let status = world.connect(i18n::Lang::En).unwrap();
+
+        let data = serde_json::to_string(&world).unwrap();
+        assert!(!data.contains(&status.token));
+
+        let restored: World = serde_json::from_str(&data).unwrap();
+        assert!(restored.entities.contains_key(&status.guid));
+        assert_eq!(restored.rooms.len(), world.rooms.len());
+        assert!(restored.sessions.is_empty());
+
+        let mut old: serde_json::Value = serde_json::from_str(&data).unwrap();
+        old["sessions"] = serde_json::json!({ "token": status.guid });
+        let restored: World = serde_json::from_value(old).unwrap();
+        assert!(restored.sessions.is_empty());
+    }
+
+    #[test]
+    fn only_the_right_token_learns_that_a_player_disappeared() {
+        let mut world = world();
+        let status = world.connect(i18n::Lang::En).unwrap();
+        let other = world.connect(i18n::Lang::En).unwrap();
+
+        assert!(world.authorize(&status.token, &status.guid).is_ok());
+        assert!(matches!(
+            world.authorize(&other.token, &status.guid),
+            Err(data_model::WorldError::Unauthorized)
+        ));
+
+        world.kick(status.guid.clone()).unwrap();
+
+        assert!(matches!(
+            world.authorize(&status.token, &status.guid),
+            Err(data_model::WorldError::Disappeared)
+        ));
+        assert!(matches!(
+            world.authorize("forged", &status.guid),
+            Err(data_model::WorldError::Unauthorized)
+        ));
+        assert!(matches!(
+            world.authorize(&other.token, &status.guid),
+            Err(data_model::WorldError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn equipment_and_potions_change_the_player_stats() {
+        let mut world = world_from(
+            r#"{"spawn_x":0,"spawn_y":0,"rooms":[{"x":0,"y":0,"items":[
+                {"kind":"weapon","dp":7},{"kind":"armour","hp":30},{"kind":"potion","hp":25}
+            ]}]}"#,
+        );
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let items: Vec<Item> = world.current_room(&guid).unwrap().items.clone();
+        for item in items.iter() {
+            world
+                .pick_up(guid.clone(), item.guid.clone(), i18n::Lang::En)
+                .unwrap();
+        }
+        let (weapon, armour, potion) = (&items[0].guid, &items[1].guid, &items[2].guid);
+
+        let inventory = world
+            .equip(guid.clone(), weapon.clone(), i18n::Lang::En)
+            .unwrap();
+        assert_eq!(inventory.dp, DEFAULT_PLAYER_DP + 7);
+
+        let inventory = world
+            .equip(guid.clone(), armour.clone(), i18n::Lang::En)
+            .unwrap();
+        assert_eq!(inventory.total_life, DEFAULT_PLAYER_HP + 30);
+        assert!(matches!(
+            world.equip(guid.clone(), potion.clone(), i18n::Lang::En),
+            Err(data_model::WorldError::Other(
+                i18n::Message::CannotEquipPotion
+            ))
+        ));
+        assert!(matches!(
+            world.use_item(guid.clone(), weapon.clone(), i18n::Lang::En),
+            Err(data_model::WorldError::Other(i18n::Message::CannotUseItem))
+        ));
+
+        world.get_entity(guid.clone()).unwrap().hp = 10;
+        let inventory = world
+            .use_item(guid.clone(), potion.clone(), i18n::Lang::En)
+            .unwrap();
+        assert_eq!(inventory.life, 35);
+        assert_eq!(inventory.items.len(), 2);
+
+        world.get_entity(guid.clone()).unwrap().hp = DEFAULT_PLAYER_HP + 30;
+        let inventory = world
+            .drop_item(guid, armour.clone(), i18n::Lang::En)
+            .unwrap();
+        assert_eq!(inventory.armour, None);
+        assert_eq!(inventory.total_life, DEFAULT_PLAYER_HP);
+        assert_eq!(inventory.life, DEFAULT_PLAYER_HP);
+    }
+
+    #[test]
+    fn experience_thresholds_raise_the_level() {
+        let mut world = world();
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let player = world.get_entity(guid).unwrap();
+        player.hp = 1;
+
+        assert!(!player.gain_xp(xp_for_level(2) - 1));
+        assert_eq!(player.level, 1);
+        assert_eq!(player.hp, 1);
+
+        assert!(player.gain_xp(1));
+        assert_eq!(player.level, 2);
+        assert_eq!(player.max_hp, DEFAULT_PLAYER_HP + LEVEL_HP_BONUS);
+        assert_eq!(player.dp, DEFAULT_PLAYER_DP + LEVEL_DP_BONUS);
+        assert_eq!(player.hp, player.max_hp);
+
+        assert!(player.gain_xp(xp_for_level(4) - player.xp));
+        assert_eq!(player.level, 4);
+        assert_eq!(player.max_hp, DEFAULT_PLAYER_HP + 3 * LEVEL_HP_BONUS);
+    }
+
+    #[test]
+    fn cleared_rooms_respawn_after_their_delay() {
+        let mut world = world_from(
+            r#"{"spawn_x":0,"spawn_y":0,"rooms":[
+                {"x":0,"y":0,"monsters":[{"dp":1,"hp":1}],"respawn_delay":1},
+                {"x":1,"y":0,"monsters":[{"dp":1,"hp":1}],"respawn_delay":0}
+            ]}"#,
+        );
+        let monsters = |world: &World, x: isize| {
+            world
+                .entities
+                .values()
+                .filter(|entity| entity.location == Coords { x, y: 0, z: 0 })
+                .count()
+        };
+        assert_eq!(monsters(&world, 1), 1);
+
+        let guids: Vec<String> = world.entities.keys().cloned().collect();
+        for guid in guids.into_iter() {
+            world.remove_entity(guid).unwrap();
+        }
+
+        world.respawn_monsters();
+        assert_eq!(monsters(&world, 0), 0);
+
+        let long_ago = time::Instant::now()
+            .checked_sub(time::Duration::from_secs(1))
+            .unwrap();
+        for room in world.rooms.values_mut() {
+            room.cleared_since = Some(long_ago);
+        }
+        world.respawn_monsters();
+        assert_eq!(monsters(&world, 0), 1);
+        assert_eq!(monsters(&world, 1), 0);
+    }
+
+    #[test]
+    fn locked_doors_open_once_their_condition_is_met() {
+        let mut world = world_from(
+            r#"{"spawn_x":0,"spawn_y":0,"rooms":[
+                {"x":0,"y":0,"monsters":[{"dp":1,"hp":1}],"exits":[
+                    {"direction":"N","lock":{"kind":"key","key":"red"}},
+                    {"direction":"E","lock":{"kind":"cleared"}},
+                    {"direction":"W","lock":{"kind":"level","level":2}}
+                ]},
+                {"x":0,"y":1},
+                {"x":1,"y":0},
+                {"x":-1,"y":0}
+            ]}"#,
+        );
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let go =
+            |world: &mut World, direction| world.r#move(guid.clone(), direction, i18n::Lang::En);
+
+        assert!(matches!(
+            go(&mut world, data_model::Direction::N),
+            Err(data_model::WorldError::Locked(i18n::Message::LockedByKey(key))) if key == "red"
+        ));
+        assert!(matches!(
+            go(&mut world, data_model::Direction::E),
+            Err(data_model::WorldError::Locked(
+                i18n::Message::LockedUntilCleared
+            ))
+        ));
+        assert!(matches!(
+            go(&mut world, data_model::Direction::W),
+            Err(data_model::WorldError::Locked(
+                i18n::Message::LockedUntilLevel(2)
+            ))
+        ));
+
+        let player = world.entities.get_mut(&guid).unwrap();
+        player.level = 2;
+        player.inventory.push(Item {
+            guid: String::from("key"),
+            description: i18n::Text::from("A red key"),
+            kind: gen_plan::ItemKind::Key {
+                key: String::from("red"),
+            },
+        });
+        let monsters: Vec<String> = world
+            .entities
+            .iter()
+            .filter(|(_, entity)| matches!(entity.r#type, EntityType::Monster(_)))
+            .map(|(guid, _)| guid.clone())
+            .collect();
+        for monster in monsters.into_iter() {
+            world.remove_entity(monster).unwrap();
+        }
+
+        for (there, back) in [
+            (data_model::Direction::N, data_model::Direction::S),
+            (data_model::Direction::E, data_model::Direction::W),
+            (data_model::Direction::W, data_model::Direction::E),
+        ] {
+            assert!(go(&mut world, there).is_ok());
+            assert!(go(&mut world, back).is_ok());
+        }
+    }
+
+    #[test]
+    fn stairs_lead_between_floors() {
+        let mut world = world_from(
+            r#"{"spawn_x":0,"spawn_y":0,"rooms":[
+                {"x":0,"y":0,"stairs":"up"},
+                {"x":0,"y":1},
+                {"x":0,"y":0,"z":1,"stairs":"down"},
+                {"x":0,"y":1,"z":1}
+            ]}"#,
+        );
+        let guid = world.connect(i18n::Lang::En).unwrap().guid;
+        let go =
+            |world: &mut World, direction| world.r#move(guid.clone(), direction, i18n::Lang::En);
+        let location = |world: &World| world.entities[&guid].location.clone();
+
+        assert!(matches!(
+            go(&mut world, data_model::Direction::D),
+            Err(data_model::WorldError::Wall)
+        ));
+        assert!(go(&mut world, data_model::Direction::U).is_ok());
+        assert_eq!(location(&world), Coords { x: 0, y: 0, z: 1 });
+
+        assert!(go(&mut world, data_model::Direction::N).is_ok());
+        assert!(matches!(
+            go(&mut world, data_model::Direction::D),
+            Err(data_model::WorldError::Wall)
+        ));
+
+        assert!(go(&mut world, data_model::Direction::S).is_ok());
+        assert!(go(&mut world, data_model::Direction::D).is_ok());
+        assert_eq!(location(&world), Coords { x: 0, y: 0, z: 0 });
+    }
+}